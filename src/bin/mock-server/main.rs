@@ -0,0 +1,216 @@
+//! A stand-in for the Slack Web API serving canned fixtures, so the client
+//! can be run end to end without a workspace:
+//!
+//!     mock-server --port 8080 &
+//!     SLACK_TOKEN=xoxp-mock slack-rs --api-url http://127.0.0.1:8080 sync -t mock
+//!
+//! The listening address is printed on the first line of stdout, which lets
+//! tests bind to port 0 and pick it up from there.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const FIXTURES: &[(&str, &str)] = &[
+    ("auth.test", include_str!("fixtures/auth.test.json")),
+    (
+        "conversations.history",
+        include_str!("fixtures/conversations.history.json"),
+    ),
+    (
+        "conversations.list",
+        include_str!("fixtures/conversations.list.json"),
+    ),
+    (
+        "conversations.replies",
+        include_str!("fixtures/conversations.replies.json"),
+    ),
+    (
+        "search.messages",
+        include_str!("fixtures/search.messages.json"),
+    ),
+    ("users.list", include_str!("fixtures/users.list.json")),
+];
+
+/// Served for `GET /` so cookie auth can scrape a token as it would from the team page.
+const HOMEPAGE: &str =
+    r#"<html><script>var boot_data = {"api_token":"xoxc-mock-token"};</script></html>"#;
+
+fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let mut port = 0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" | "--port" => {
+                port = args
+                    .next()
+                    .and_then(|p| p.parse().ok())
+                    .expect("--port needs a number")
+            }
+            _ => {
+                eprintln!("usage: mock-server [--port <port>]");
+                std::process::exit(2);
+            }
+        }
+    }
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("http://{}", listener.local_addr()?);
+    std::io::stdout().flush()?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            if let Err(e) = handle(stream) {
+                eprintln!("mock-server: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let target = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let url = url::Url::parse(&format!("http://localhost{}", target))
+        .unwrap_or_else(|_| url::Url::parse("http://localhost/").unwrap());
+    let mut params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    let is_form = headers
+        .get("content-type")
+        .is_some_and(|t| t.starts_with("application/x-www-form-urlencoded"));
+    if is_form {
+        params.extend(url::form_urlencoded::parse(&body).into_owned());
+    }
+    eprintln!("mock-server: {} {:?}", url.path(), params);
+
+    let (content_type, response) = match url.path().strip_prefix("/api/") {
+        None => ("text/html", HOMEPAGE.to_string()),
+        Some(_) if !headers.contains_key("authorization") => (
+            "application/json",
+            json!({"ok": false, "error": "not_authed"}).to_string(),
+        ),
+        Some(method) => ("application/json", respond(method, &params).to_string()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        content_type,
+        response.len(),
+        response
+    )?;
+    stream.flush()
+}
+
+fn respond(method: &str, params: &HashMap<String, String>) -> Value {
+    let fixture = match FIXTURES.iter().find(|(name, _)| *name == method) {
+        Some((_, fixture)) => fixture,
+        None => return json!({"ok": false, "error": "unknown_method"}),
+    };
+    let mut response: Value = serde_json::from_str(fixture).expect("invalid fixture");
+    let param = |name: &str| params.get(name).map(String::as_str).unwrap_or_default();
+    let ts = |m: &Value| {
+        m["ts"]
+            .as_str()
+            .unwrap_or_default()
+            .parse::<f64>()
+            .unwrap_or(0.0)
+    };
+    match method {
+        "conversations.history" => {
+            let oldest = param("oldest").parse::<f64>().unwrap_or(f64::MIN);
+            let latest = param("latest").parse::<f64>().unwrap_or(f64::MAX);
+            let inclusive = param("inclusive") == "true";
+            let messages = response["messages"].as_array_mut().unwrap();
+            messages.retain(|m| {
+                let ts = ts(m);
+                (ts > oldest && ts < latest) || (inclusive && (ts == oldest || ts == latest))
+            });
+            // newest first, like Slack
+            messages.sort_by(|a, b| ts(b).total_cmp(&ts(a)));
+            paginate(&mut response, "messages", params);
+        }
+        "conversations.replies" => {
+            let thread = param("ts").to_string();
+            let messages = response["messages"].as_array_mut().unwrap();
+            messages.retain(|m| m["thread_ts"].as_str() == Some(thread.as_str()));
+            if messages.is_empty() {
+                // a message without replies is a thread of one
+                let history: Value =
+                    serde_json::from_str(include_str!("fixtures/conversations.history.json"))
+                        .expect("invalid fixture");
+                match history["messages"]
+                    .as_array()
+                    .and_then(|h| h.iter().find(|m| m["ts"].as_str() == Some(thread.as_str())))
+                {
+                    Some(message) => messages.push(message.clone()),
+                    None => return json!({"ok": false, "error": "thread_not_found"}),
+                }
+            }
+            paginate(&mut response, "messages", params);
+        }
+        "conversations.list" => paginate(&mut response, "channels", params),
+        "users.list" => paginate(&mut response, "members", params),
+        "search.messages" => {
+            let query = param("query").to_lowercase();
+            let count = param("count").parse().unwrap_or(20);
+            let matches = response["messages"]["matches"].as_array_mut().unwrap();
+            matches.retain(|m| {
+                let text = m["text"].as_str().unwrap_or_default().to_lowercase();
+                query.split_whitespace().all(|word| text.contains(word))
+            });
+            matches.truncate(count);
+            response["query"] = json!(param("query"));
+        }
+        _ => {}
+    }
+    response
+}
+
+/// Serves `items` a page at a time following `limit`/`cursor`, where the
+/// cursor is just the offset of the next page.
+fn paginate(response: &mut Value, items: &str, params: &HashMap<String, String>) {
+    let all = response[items].as_array().cloned().unwrap_or_default();
+    let offset = params
+        .get("cursor")
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(0usize)
+        .min(all.len());
+    let limit = params
+        .get("limit")
+        .and_then(|l| l.parse().ok())
+        .filter(|l| *l > 0)
+        .unwrap_or(100usize);
+    let end = (offset + limit).min(all.len());
+    response[items] = Value::Array(all[offset..end].to_vec());
+    let next_cursor = if end < all.len() {
+        end.to_string()
+    } else {
+        String::new()
+    };
+    response["has_more"] = json!(end < all.len());
+    response["response_metadata"] = json!({ "next_cursor": next_cursor });
+}
@@ -2,143 +2,450 @@ use sqlite::State;
 
 use crate::cache::cache::Cache;
 use crate::model::domain::*;
-use std::error::Error;
+use crate::error::{Error, Result};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-const DB_PATH: &str = "/Users/vishalsharma/.slack/info.db";
+/// Environment variable overriding the cache location.
+pub const DB_ENV_VAR: &str = "SLACK_RS_DB";
+
+/// Path that keeps the cache in memory for the lifetime of the process.
+pub const MEMORY_PATH: &str = ":memory:";
+
+/// Resolves the cache location from the `--db` flag, then `SLACK_RS_DB`, then
+/// `$XDG_CACHE_HOME/slack-rs/info.db` (or the platform cache dir).
+pub fn db_path(flag: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = flag {
+        return Ok(path.to_path_buf());
+    }
+    if let Some(path) = std::env::var_os(DB_ENV_VAR).filter(|x| !x.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let cache_dir = dirs::cache_dir()
+        .ok_or_else(|| Error::Cache("could not find a cache directory, pass --db".into()))?;
+    Ok(cache_dir.join("slack-rs").join("info.db"))
+}
+
+/// Schema migrations in order. Migration `n` brings the database to
+/// `PRAGMA user_version = n + 1`; applied ones are never run again, so new
+/// steps must be appended rather than edited in place.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema, kept idempotent for databases that predate versioning
+    "
+    CREATE TABLE IF NOT EXISTS channels (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        team TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS users (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        team TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS users_v2 (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        email TEXT NOT NULL,
+        team TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS audit (
+        id INTEGER PRIMARY KEY,
+        action TEXT NOT NULL,
+        table_name TEXT NOT NULL,
+        timestamp TEXT NOT NULL
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_channels_team ON channels (team);
+    CREATE INDEX IF NOT EXISTS idx_users_team ON users (team);
+    ",
+    // 2: retire users in favour of users_v2 and drop the unused audit table
+    "
+    INSERT OR IGNORE INTO users_v2 (id, name, email, team)
+        SELECT id, name, '', team FROM users;
+    DROP INDEX IF EXISTS idx_users_team;
+    DROP TABLE IF EXISTS users;
+    DROP TABLE IF EXISTS audit;
+    CREATE INDEX IF NOT EXISTS idx_users_v2_team ON users_v2 (team);
+    ",
+    // 3: key users and channels by (team, id) so several workspaces can share a db
+    "
+    CREATE TABLE channels_new (
+        team TEXT NOT NULL,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (team, id)
+    );
+    INSERT OR REPLACE INTO channels_new (team, id, name)
+        SELECT team, id, name FROM channels;
+    DROP TABLE channels;
+    ALTER TABLE channels_new RENAME TO channels;
+
+    CREATE TABLE users_v2_new (
+        team TEXT NOT NULL,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        email TEXT NOT NULL,
+        PRIMARY KEY (team, id)
+    );
+    INSERT OR REPLACE INTO users_v2_new (team, id, name, email)
+        SELECT team, id, name, email FROM users_v2;
+    DROP TABLE users_v2;
+    ALTER TABLE users_v2_new RENAME TO users_v2;
+    ",
+    // 4: profile timezone, filled in by the next sync
+    "
+    ALTER TABLE users_v2 ADD COLUMN tz TEXT NOT NULL DEFAULT '';
+    ",
+];
 
 /// sqlite
-/// users table-> user_id, user_name, team_name
-/// channels table-> channel_id, channel_name, team_name
+/// users_v2 table-> team_name, user_id, user_name, email, tz, keyed by (team_name, user_id)
+/// channels table-> team_name, channel_id, channel_name, keyed by (team_name, channel_id)
 impl Cache for InMemoryCache {
     fn get_channel(&self, team: &str, id: &str) -> Option<Channel> {
+        self.find_channel(team, id).unwrap_or_else(|e| {
+            log::warn!("could not read channel {} from cache: {}", id, e);
+            None
+        })
+    }
+
+    fn get_user(&self, team: &str, id: &str) -> Option<User> {
+        self.find_user(team, id).unwrap_or_else(|e| {
+            log::warn!("could not read user {} from cache: {}", id, e);
+            None
+        })
+    }
+
+    fn get_channel_id(&self, team: &str, name: &str) -> Option<String> {
+        self.find_channel_id(team, name).unwrap_or_else(|e| {
+            log::warn!("could not look up channel #{} in cache: {}", name, e);
+            None
+        })
+    }
+
+    fn get_user_id(&self, team: &str, name: &str) -> Option<String> {
+        self.find_user_id(team, name).unwrap_or_else(|e| {
+            log::warn!("could not look up user @{} in cache: {}", name, e);
+            None
+        })
+    }
+
+    fn sync_users(&self, team: &str, users: Vec<User>) -> Result<()> {
+        self.transaction(|| {
+            let mut statement = self.connection.prepare(
+                "INSERT OR REPLACE INTO users_v2 (id, name, team, email, tz) VALUES (?, ?, ?, ?, ?)",
+            )?;
+            for user in &users {
+                statement.reset()?;
+                statement.bind((1, user.id.as_str()))?;
+                statement.bind((2, user.name.as_str()))?;
+                statement.bind((3, team))?;
+                statement.bind((4, user.email.as_str()))?;
+                statement.bind((5, user.tz.as_str()))?;
+                statement.next()?;
+            }
+            Ok(())
+        })
+    }
+
+    fn sync_channels(&self, team: &str, channels: Vec<Channel>) -> Result<()> {
+        self.transaction(|| {
+            let mut statement = self
+                .connection
+                .prepare("INSERT OR REPLACE INTO channels (id, name, team) VALUES (?, ?, ?)")?;
+            for channel in &channels {
+                statement.reset()?;
+                statement.bind((1, channel.id.as_str()))?;
+                statement.bind((2, channel.name.as_str()))?;
+                statement.bind((3, team))?;
+                statement.next()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+pub struct InMemoryCache {
+    connection: sqlite::Connection,
+}
+
+impl InMemoryCache {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(path: &Path) -> Result<Box<dyn Cache>> {
+        if path == Path::new(MEMORY_PATH) {
+            return InMemoryCache::in_memory();
+        }
+        if let Some(parent_dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent_dir).map_err(|e| {
+                Error::Cache(format!("could not create {}: {}", parent_dir.display(), e))
+            })?;
+        }
+        log::info!("cache db: {}", path.display());
+        let connection = sqlite::open(path)?;
+        let db = Box::new(InMemoryCache { connection });
+        db.migrate()?;
+        Ok(db)
+    }
+
+    /// Opens a cache that lives only as long as the process, useful in tests.
+    pub fn in_memory() -> Result<Box<dyn Cache>> {
+        let connection = sqlite::open(MEMORY_PATH)?;
+        let db = Box::new(InMemoryCache { connection });
+        db.migrate()?;
+        Ok(db)
+    }
+
+    fn find_channel(&self, team: &str, id: &str) -> Result<Option<Channel>> {
         let mut statement = self
             .connection
-            .prepare("SELECT * FROM channels WHERE id = ? and team = ?")
-            .unwrap();
-        statement.bind((1, id)).unwrap();
-        statement.bind((2, team)).unwrap();
-        if let State::Row = statement.next().unwrap() {
-            let id = statement.read::<String, usize>(0).unwrap();
-            let name = statement.read(1).unwrap();
-            return Some(Channel {
+            .prepare("SELECT id, name FROM channels WHERE id = ? and team = ?")?;
+        statement.bind((1, id))?;
+        statement.bind((2, team))?;
+        if let State::Row = statement.next()? {
+            let id = statement.read::<String, usize>(0)?;
+            let name = statement.read(1)?;
+            return Ok(Some(Channel {
                 id,
                 name,
                 is_channel: true,
-            });
+            }));
         }
-        None
+        Ok(None)
     }
 
-    fn get_user(&self, team: &str, id: &str) -> Option<User> {
+    fn find_channel_id(&self, team: &str, name: &str) -> Result<Option<String>> {
         let mut statement = self
             .connection
-            .prepare("SELECT * FROM users WHERE id = ? and team = ?")
-            .unwrap();
-        statement.bind((1, id)).unwrap();
-        statement.bind((2, team)).unwrap();
-        if let State::Row = statement.next().unwrap() {
-            let id = statement.read::<String, usize>(0).unwrap();
-            let name = statement.read(1).unwrap();
-            return Some(User { id, name, email: "email".to_string() });
+            .prepare("SELECT id FROM channels WHERE name = ? and team = ?")?;
+        statement.bind((1, name))?;
+        statement.bind((2, team))?;
+        if let State::Row = statement.next()? {
+            return Ok(Some(statement.read::<String, usize>(0)?));
         }
-        None
+        Ok(None)
     }
 
-    // fn get_channel_id(&self, team: &str, name: &str) -> Option<String> {
-    //     None
-    // }
+    fn find_user(&self, team: &str, id: &str) -> Result<Option<User>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, name, email, tz FROM users_v2 WHERE id = ? and team = ?")?;
+        statement.bind((1, id))?;
+        statement.bind((2, team))?;
+        if let State::Row = statement.next()? {
+            let id = statement.read::<String, usize>(0)?;
+            let name = statement.read(1)?;
+            let email = statement.read(2)?;
+            let tz = statement.read(3)?;
+            return Ok(Some(User {
+                id,
+                name,
+                email,
+                tz,
+            }));
+        }
+        Ok(None)
+    }
 
-    // fn get_user_id(&self, team: &str, name: &str) -> Option<String> {
-    //     None
-    // }
+    fn find_user_id(&self, team: &str, name: &str) -> Result<Option<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM users_v2 WHERE name = ? and team = ?")?;
+        statement.bind((1, name))?;
+        statement.bind((2, team))?;
+        if let State::Row = statement.next()? {
+            return Ok(Some(statement.read::<String, usize>(0)?));
+        }
+        Ok(None)
+    }
 
-    fn sync_users(&self, team: &str, users: Vec<User>) -> Result<(), Box<dyn Error>> {
+    /// Runs `f` inside a transaction, rolling back if it fails.
+    fn transaction<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
         self.connection.execute("BEGIN TRANSACTION")?;
-        for user in &users {
-            let query = format!(
-                "INSERT OR REPLACE INTO users (id, name, team) VALUES ('{}', '{}', '{}')",
-                user.id, user.name, team
-            );
-            self.connection.execute(&query)?;
-        }
-        for user in &users {
-            let query = format!(
-                "INSERT OR REPLACE INTO users_v2 (id, name, team, email) VALUES ('{}', '{}', '{}','{}')",
-                user.id, user.name, team, user.email
-            );
-            self.connection.execute(&query)?;
+        match f() {
+            Ok(()) => {
+                self.connection.execute("COMMIT")?;
+                Ok(())
+            }
+            Err(e) => {
+                if let Err(rollback) = self.connection.execute("ROLLBACK") {
+                    log::warn!("rollback failed: {}", rollback);
+                }
+                Err(e)
+            }
         }
-        self.connection.execute("COMMIT")?;
-        Ok(())
     }
 
-    fn sync_channels(&self, team: &str, channels: Vec<Channel>) -> Result<(), Box<dyn Error>> {
-        self.connection.execute("BEGIN TRANSACTION")?;
-        for channel in channels {
-            let query = format!(
-                "INSERT OR REPLACE INTO channels (id, name, team) VALUES ('{}', '{}', '{}')",
-                channel.id, channel.name, team
-            );
-            self.connection.execute(&query)?;
+    pub fn migrate(&self) -> Result<()> {
+        let version = self.schema_version()?;
+        if version > MIGRATIONS.len() {
+            return Err(Error::Cache(format!(
+                "cache schema version {} is newer than this build supports ({})",
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            log::info!("migrating cache schema to version {}", index + 1);
+            self.transaction(|| {
+                self.connection.execute(migration)?;
+                self.connection
+                    .execute(format!("PRAGMA user_version = {}", index + 1))?;
+                Ok(())
+            })?;
         }
-        self.connection.execute("COMMIT")?;
         Ok(())
     }
-}
 
-pub struct InMemoryCache {
-    connection: sqlite::Connection,
+    fn schema_version(&self) -> Result<usize> {
+        let mut statement = self.connection.prepare("PRAGMA user_version")?;
+        statement.next()?;
+        Ok(statement.read::<i64, usize>(0)? as usize)
+    }
 }
 
-impl InMemoryCache {
-    pub fn new() -> Result<Box<dyn Cache>, Box<dyn Error>> {
-        if let Some(parent_dir) = Path::new(DB_PATH).parent() {
-            fs::create_dir_all(parent_dir).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The schema databases had before migrations were versioned.
+    const LEGACY_SCHEMA: &str = "
+    CREATE TABLE channels (id TEXT PRIMARY KEY, name TEXT NOT NULL, team TEXT NOT NULL);
+    CREATE TABLE users (id TEXT PRIMARY KEY, name TEXT NOT NULL, team TEXT NOT NULL);
+    CREATE TABLE users_v2 (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        email TEXT NOT NULL,
+        team TEXT NOT NULL
+    );
+    CREATE TABLE audit (
+        id INTEGER PRIMARY KEY,
+        action TEXT NOT NULL,
+        table_name TEXT NOT NULL,
+        timestamp TEXT NOT NULL
+    );
+    CREATE INDEX idx_channels_team ON channels (team);
+    CREATE INDEX idx_users_team ON users (team);
+    INSERT INTO channels VALUES ('C1', 'general', 'acme');
+    INSERT INTO users VALUES ('U1', 'alice', 'acme');
+    ";
+
+    fn cache() -> InMemoryCache {
+        InMemoryCache {
+            connection: sqlite::open(MEMORY_PATH).unwrap(),
         }
-        if !Path::new(DB_PATH).exists() {
-            std::fs::File::create(DB_PATH).unwrap();
+    }
+
+    fn tables(cache: &InMemoryCache) -> Vec<String> {
+        let mut statement = cache
+            .connection
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        let mut tables = Vec::new();
+        while let State::Row = statement.next().unwrap() {
+            tables.push(statement.read::<String, usize>(0).unwrap());
         }
-        let connection = sqlite::open(DB_PATH)?;
-        let db = Box::new(InMemoryCache { connection });
-        db.migrate()?;
-        Ok(db)
+        tables
     }
 
-    pub fn migrate(&self) -> Result<(), Box<dyn Error>> {
-        let query = "
-        CREATE TABLE IF NOT EXISTS channels (
-            id TEXT PRIMARY KEY,
-            name TEXT NOT NULL,
-            team TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS users (
-            id TEXT PRIMARY KEY,
-            name TEXT NOT NULL,
-            team TEXT NOT NULL
-        );
-
-        CREATE TABLE IF NOT EXISTS users_v2 (
-            id TEXT PRIMARY KEY,
-            name TEXT NOT NULL,
-            email TEXT NOT NULL,
-            team TEXT NOT NULL
-        );
-        
-        CREATE TABLE IF NOT EXISTS audit (
-            id INTEGER PRIMARY KEY,
-            action TEXT NOT NULL,
-            table_name TEXT NOT NULL,
-            timestamp TEXT NOT NULL
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_channels_team ON channels (team);
-        CREATE INDEX IF NOT EXISTS idx_users_team ON users (team);
-        ";
-
-        self.connection.execute(query)?;
-        Ok(())
+    #[test]
+    fn migrates_a_legacy_database() {
+        let cache = cache();
+        cache.connection.execute(LEGACY_SCHEMA).unwrap();
+        cache.migrate().unwrap();
+        cache.migrate().unwrap();
+
+        assert_eq!(cache.schema_version().unwrap(), MIGRATIONS.len());
+        assert_eq!(tables(&cache), ["channels", "users_v2"]);
+        let user = cache.find_user("acme", "U1").unwrap().unwrap();
+        assert_eq!((user.name.as_str(), user.email.as_str()), ("alice", ""));
+        assert_eq!(user.tz, "");
+        let channel = cache.find_channel("acme", "C1").unwrap().unwrap();
+        assert_eq!(channel.name, "general");
+    }
+
+    fn user(id: &str, name: &str) -> User {
+        User {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..User::default()
+        }
+    }
+
+    fn channel(id: &str, name: &str) -> Channel {
+        Channel {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_channel: true,
+        }
+    }
+
+    #[test]
+    fn syncs_names_with_quotes() {
+        let cache = cache();
+        cache.migrate().unwrap();
+        cache
+            .sync_users("acme", vec![user("U1", "o'brien")])
+            .unwrap();
+        cache
+            .sync_channels("acme", vec![channel("C1", "it's-on")])
+            .unwrap();
+        assert_eq!(cache.get_user("acme", "U1").unwrap().name, "o'brien");
+        assert_eq!(cache.get_user_id("acme", "o'brien").as_deref(), Some("U1"));
+        assert_eq!(cache.get_channel("acme", "C1").unwrap().name, "it's-on");
+    }
+
+    #[test]
+    fn failed_sync_rolls_back() {
+        let cache = cache();
+        cache.migrate().unwrap();
+        cache
+            .connection
+            .execute(
+                "CREATE TRIGGER reject BEFORE INSERT ON users_v2 WHEN NEW.name = 'bad'
+                BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+            )
+            .unwrap();
+        let users = vec![user("U1", "alice"), user("U2", "bad")];
+        assert!(cache.sync_users("acme", users).is_err());
+        assert!(cache.get_user("acme", "U1").is_none());
+
+        // no transaction is left open, so the next sync starts its own
+        cache.sync_users("acme", vec![user("U1", "alice")]).unwrap();
+        cache
+            .sync_channels("acme", vec![channel("C1", "general")])
+            .unwrap();
+        assert_eq!(cache.get_user("acme", "U1").unwrap().name, "alice");
+        assert_eq!(cache.get_channel("acme", "C1").unwrap().name, "general");
+    }
+
+    #[test]
+    fn keeps_each_teams_rows_apart() {
+        let cache = cache();
+        cache.migrate().unwrap();
+        for (team, name, room) in [("acme", "alice", "general"), ("globex", "hank", "ops")] {
+            cache.sync_users(team, vec![user("U1", name)]).unwrap();
+            cache
+                .sync_channels(team, vec![channel("C1", room)])
+                .unwrap();
+        }
+        assert_eq!(cache.get_user("acme", "U1").unwrap().name, "alice");
+        assert_eq!(cache.get_user("globex", "U1").unwrap().name, "hank");
+        assert_eq!(cache.get_channel("acme", "C1").unwrap().name, "general");
+        assert_eq!(cache.get_channel("globex", "C1").unwrap().name, "ops");
+        assert!(cache.get_user("initech", "U1").is_none());
+    }
+
+    #[test]
+    fn migrates_a_new_database() {
+        let cache = cache();
+        cache.migrate().unwrap();
+        assert_eq!(cache.schema_version().unwrap(), MIGRATIONS.len());
+        assert_eq!(tables(&cache), ["channels", "users_v2"]);
     }
 }
@@ -1,4 +1,9 @@
+use crate::export::{Markup, Scope};
+use crate::output::Format;
+use crate::time::TimeFormat;
 use clap::{Parser, Subcommand};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 pub struct Cli {
@@ -10,27 +15,139 @@ pub struct Cli {
     #[clap(short, long)]
     #[clap(default_value = "false")]
     pub debug: bool,
+    /// File containing a user (xoxp-) or bot (xoxb-) token, used instead of the
+    /// desktop client's cookie. Defaults to SLACK_TOKEN, then the keyring.
+    #[clap(long, global = true)]
+    pub token_file: Option<PathBuf>,
+    /// Path of the sqlite cache, or :memory: to skip persisting it.
+    /// Defaults to SLACK_RS_DB, then $XDG_CACHE_HOME/slack-rs/info.db.
+    #[clap(long, global = true)]
+    pub db: Option<PathBuf>,
+    /// Base url to send requests to instead of https://{team}.slack.com,
+    /// e.g. a local mock server. Defaults to SLACK_API_URL.
+    #[clap(long, global = true)]
+    pub api_url: Option<String>,
+    /// Save every api request and response to this directory, without
+    /// credentials, so it can be replayed offline.
+    #[clap(long, global = true, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+    /// Answer api requests from a directory written by --record instead of
+    /// calling slack. No authentication is needed.
+    #[clap(long, global = true, value_name = "DIR", conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+    /// Print messages as colored text, or as json, ndjson or csv records with
+    /// users and channels resolved and a permalink to each message.
+    #[clap(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    /// How to show when each message was sent
+    #[clap(long, global = true, value_enum, default_value_t = TimeFormat::Local)]
+    pub time_format: TimeFormat,
+    /// Timezone for message times: local, an IANA name like Europe/London, or
+    /// @name for that user's profile timezone once `sync` has cached it.
+    #[clap(long, global = true, default_value = "local")]
+    pub tz: String,
+    /// Group messages under a header for each day
+    #[clap(long, global = true)]
+    pub date_headers: bool,
 }
 
 #[derive(Subcommand)]
 pub enum SubCommand {
     Read {
+        /// Message link to start from, or #channel-name together with --team
         arg: String,
+        /// Messages to fetch per request
         #[clap(short, long)]
         #[clap(default_value = "10")]
         count: u32,
+        /// Stop after this many messages, keeping the newest. Defaults to a
+        /// single page when there is no link or --since to start from.
+        #[clap(short, long)]
+        limit: Option<usize>,
+        /// Team to read from, needed with #channel-name and with links that
+        /// only carry the team id
+        #[clap(short, long)]
+        team: Option<String>,
+        /// Oldest message to read: a duration back from now (2h, 3d), a date
+        /// ('2026-10-01 09:00') or a slack timestamp. Overrides the link's.
+        #[clap(long)]
+        since: Option<String>,
+        /// Newest message to read, in the same forms as --since
+        #[clap(long)]
+        until: Option<String>,
     },
     ReadThreaded {
+        /// Message link to start from, or #channel-name together with --team
         arg: String,
+        /// Messages to fetch per request
         #[clap(short, long)]
         #[clap(default_value = "10")]
         count: u32,
+        /// Stop after this many messages, keeping the newest. Defaults to a
+        /// single page when there is no link or --since to start from.
+        #[clap(short, long)]
+        limit: Option<usize>,
+        /// Team to read from, needed with #channel-name and with links that
+        /// only carry the team id
+        #[clap(short, long)]
+        team: Option<String>,
+        /// Oldest message to read: a duration back from now (2h, 3d), a date
+        /// ('2026-10-01 09:00') or a slack timestamp. Overrides the link's.
+        #[clap(long)]
+        since: Option<String>,
+        /// Newest message to read, in the same forms as --since
+        #[clap(long)]
+        until: Option<String>,
+        /// Threads to fetch at once. All of them share the rate limit.
+        #[clap(long)]
+        #[clap(default_value = "4")]
+        concurrency: usize,
     },
     Thread {
+        /// Link to the thread's first message or to any reply in it
+        arg: String,
+        /// Stop after this many messages, the first one included. Reads the
+        /// whole thread by default.
+        #[clap(short, long)]
+        count: Option<NonZeroUsize>,
+        /// Team the thread is in, needed with links that only carry the team id
+        #[clap(short, long)]
+        team: Option<String>,
+    },
+    /// Write a thread or part of a channel as a Markdown or HTML transcript
+    Export {
+        /// Message link, or #channel-name together with --team
         arg: String,
+        /// What to export. Defaults to the linked thread, or the channel's
+        /// messages when there is no message in the link or a --since/--until.
+        #[clap(short, long, value_enum)]
+        scope: Option<Scope>,
+        #[clap(long, value_enum, default_value_t = Markup::Markdown)]
+        to: Markup,
+        /// File to write the transcript to instead of stdout
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Messages to fetch per request
         #[clap(short, long)]
         #[clap(default_value = "10")]
         count: u32,
+        /// Stop after this many channel messages, keeping the newest
+        #[clap(short, long)]
+        limit: Option<usize>,
+        /// Team to export from, needed with #channel-name and with links that
+        /// only carry the team id
+        #[clap(short, long)]
+        team: Option<String>,
+        /// Oldest message to export, in the same forms as read's --since
+        #[clap(long)]
+        since: Option<String>,
+        /// Newest message to export, in the same forms as --since
+        #[clap(long)]
+        until: Option<String>,
+        /// Threads to fetch at once with --scope read-threaded
+        #[clap(long)]
+        #[clap(default_value = "4")]
+        concurrency: usize,
     },
     Search {
         #[clap(short, long)]
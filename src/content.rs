@@ -0,0 +1,376 @@
+use crate::model::message::{Attachment, Block, Element, File, Message, TextObject};
+
+/// Everything a message shows, as mrkdwn: its blocks when they hold any text,
+/// since `text` is then only the notification fallback, else its text.
+/// Attachments and files follow.
+pub fn mrkdwn(message: &Message) -> String {
+    let blocks = blocks(&message.blocks);
+    let mut parts = vec![if blocks.is_empty() {
+        message.text.clone()
+    } else {
+        blocks
+    }];
+    parts.extend(message.attachments.iter().map(attachment));
+    parts.extend(message.files.iter().map(file));
+    parts.retain(|part| !part.trim().is_empty());
+    parts.join("\n")
+}
+
+fn blocks(blocks: &[Block]) -> String {
+    let lines: Vec<String> = blocks
+        .iter()
+        .map(block)
+        .filter(|text| !text.trim().is_empty())
+        .collect();
+    lines.join("\n")
+}
+
+fn block(block: &Block) -> String {
+    match block.type_field.as_str() {
+        "header" => block
+            .text
+            .as_ref()
+            .map(|text| emphasize(&text_object(text), '*'))
+            .unwrap_or_default(),
+        "section" => {
+            let mut lines: Vec<String> = block.text.iter().map(text_object).collect();
+            lines.extend(block.fields.iter().map(text_object));
+            lines.join("\n")
+        }
+        "context" => block
+            .elements
+            .iter()
+            .filter_map(element_text)
+            .collect::<Vec<_>>()
+            .join(" · "),
+        "rich_text" => block
+            .elements
+            .iter()
+            .map(rich_text)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "image" => match (&block.image_url, &block.alt_text) {
+            (Some(url), Some(alt)) => format!("<{}|{}>", url, escape(alt)),
+            (Some(url), None) => format!("<{}>", url),
+            _ => String::new(),
+        },
+        "divider" => "---".to_string(),
+        // actions and inputs are buttons and forms, nothing to read
+        _ => String::new(),
+    }
+}
+
+fn text_object(text: &TextObject) -> String {
+    match text.type_field.as_str() {
+        "plain_text" => escape(&text.text),
+        _ => text.text.clone(),
+    }
+}
+
+/// Text of a context element: mrkdwn or plain text, or an image's alt text.
+fn element_text(element: &Element) -> Option<String> {
+    match element.type_field.as_str() {
+        "mrkdwn" => element.text.as_ref()?.as_str().map(str::to_string),
+        "plain_text" => element.text.as_ref()?.as_str().map(escape),
+        "image" => element.alt_text.as_deref().map(escape),
+        _ => None,
+    }
+}
+
+/// A top level rich text element: a section, list, code block or quote.
+fn rich_text(element: &Element) -> String {
+    match element.type_field.as_str() {
+        "rich_text_list" => {
+            let ordered = element.style.as_ref().and_then(|s| s.as_str()) == Some("ordered");
+            let indent = "    ".repeat(element.indent);
+            element
+                .elements
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let bullet = if ordered {
+                        format!("{}.", i + 1)
+                    } else {
+                        "•".to_string()
+                    };
+                    format!("{}{} {}", indent, bullet, inline(&item.elements))
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        "rich_text_preformatted" => format!("```{}```", inline(&element.elements)),
+        "rich_text_quote" => inline(&element.elements)
+            .split('\n')
+            .map(|line| format!("&gt; {}", line))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => inline(&element.elements),
+    }
+}
+
+fn inline(elements: &[Element]) -> String {
+    elements.iter().map(inline_element).collect()
+}
+
+fn inline_element(element: &Element) -> String {
+    let text = element
+        .text
+        .as_ref()
+        .and_then(|text| text.as_str())
+        .map(escape);
+    match element.type_field.as_str() {
+        "text" => {
+            let style = |name: &str| {
+                element
+                    .style
+                    .as_ref()
+                    .and_then(|style| style[name].as_bool())
+                    .unwrap_or(false)
+            };
+            let mut text = text.unwrap_or_default();
+            if style("code") {
+                text = emphasize(&text, '`');
+            }
+            for (name, mark) in [("bold", '*'), ("italic", '_'), ("strike", '~')] {
+                if style(name) {
+                    text = emphasize(&text, mark);
+                }
+            }
+            text
+        }
+        "link" => match (&element.url, text) {
+            (Some(url), Some(text)) => format!("<{}|{}>", url, text),
+            (Some(url), None) => format!("<{}>", url),
+            (None, text) => text.unwrap_or_default(),
+        },
+        "user" => format!("<@{}>", element.user_id.as_deref().unwrap_or_default()),
+        "channel" => format!("<#{}>", element.channel_id.as_deref().unwrap_or_default()),
+        "usergroup" => format!(
+            "<!subteam^{}>",
+            element.usergroup_id.as_deref().unwrap_or_default()
+        ),
+        "broadcast" => format!("<!{}>", element.range.as_deref().unwrap_or("here")),
+        "emoji" => format!(":{}:", element.name.as_deref().unwrap_or_default()),
+        "date" => element.fallback.as_deref().map(escape).unwrap_or_default(),
+        _ => text.unwrap_or_default(),
+    }
+}
+
+/// Legacy attachments read as a quote under the message, like Slack's
+/// colored bar.
+fn attachment(attachment: &Attachment) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    if !attachment.pretext.is_empty() {
+        lines.push(attachment.pretext.clone());
+    }
+    let mut quoted: Vec<String> = Vec::new();
+    if !attachment.author_name.is_empty() {
+        quoted.push(attachment.author_name.clone());
+    }
+    match (attachment.title.as_str(), attachment.title_link.as_str()) {
+        ("", _) => {}
+        (title, "") => quoted.push(emphasize(title, '*')),
+        (title, link) => quoted.push(emphasize(&format!("<{}|{}>", link, title), '*')),
+    }
+    if !attachment.text.is_empty() {
+        quoted.push(attachment.text.clone());
+    }
+    for field in &attachment.fields {
+        quoted.push(match field.title.as_str() {
+            "" => field.value.clone(),
+            title => format!("{}: {}", emphasize(title, '*'), field.value),
+        });
+    }
+    let blocks = blocks(&attachment.blocks);
+    if !blocks.is_empty() {
+        quoted.push(blocks);
+    }
+    if !attachment.footer.is_empty() {
+        quoted.push(attachment.footer.clone());
+    }
+    if quoted.is_empty() && !attachment.fallback.is_empty() {
+        quoted.push(attachment.fallback.clone());
+    }
+    lines.extend(
+        quoted
+            .iter()
+            .flat_map(|text| text.split('\n'))
+            .map(|line| format!("&gt; {}", line)),
+    );
+    lines.join("\n")
+}
+
+fn file(file: &File) -> String {
+    let name = [&file.title, &file.name, &file.id]
+        .into_iter()
+        .find(|name| !name.is_empty())
+        .map(|name| escape(name))
+        .unwrap_or_default();
+    if file.permalink.is_empty() {
+        format!("file: {}", name)
+    } else {
+        format!("file: <{}|{}>", file.permalink, name)
+    }
+}
+
+/// Wraps `text` in `mark`, leaving its surrounding spaces outside so the
+/// markup still closes.
+fn emphasize(text: &str, mark: char) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = start + trimmed.len();
+    format!(
+        "{}{}{}{}{}",
+        &text[..start],
+        mark,
+        trimmed,
+        mark,
+        &text[end..]
+    )
+}
+
+/// Block text is raw, mrkdwn has `&`, `<` and `>` escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn render(mut message: Value) -> String {
+        message["type"] = json!("message");
+        mrkdwn(&serde_json::from_value(message).unwrap())
+    }
+
+    fn text(text: &str) -> Value {
+        json!({"type": "text", "text": text})
+    }
+
+    fn section(elements: Value) -> Value {
+        json!({"type": "rich_text_section", "elements": elements})
+    }
+
+    #[test]
+    fn renders_rich_text() {
+        let cases = [
+            (
+                json!({"type": "rich_text_list", "style": "bullet", "elements": [
+                    section(json!([text("one")])),
+                    section(json!([text("two")])),
+                ]}),
+                "• one\n• two",
+            ),
+            (
+                json!({"type": "rich_text_list", "style": "ordered", "indent": 1, "elements": [
+                    section(json!([text("first")])),
+                    section(json!([text("second")])),
+                ]}),
+                "    1. first\n    2. second",
+            ),
+            (
+                json!({"type": "rich_text_preformatted", "elements": [text("let x = 1;\nx < 2")]}),
+                "```let x = 1;\nx &lt; 2```",
+            ),
+            (
+                json!({"type": "rich_text_quote", "elements": [text("one\ntwo")]}),
+                "&gt; one\n&gt; two",
+            ),
+            (
+                section(json!([
+                    text("plain "),
+                    {"type": "text", "text": "loud ", "style": {"bold": true, "italic": true}},
+                    {"type": "text", "text": "x", "style": {"code": true, "bold": true}},
+                    {"type": "text", "text": " ", "style": {"strike": true}},
+                ])),
+                "plain _*loud*_ *`x`* ",
+            ),
+            (
+                section(json!([
+                    {"type": "user", "user_id": "U1"},
+                    text(" see "),
+                    {"type": "link", "url": "https://example.com", "text": "the docs"},
+                    text(" "),
+                    {"type": "link", "url": "https://example.com"},
+                    text(" "),
+                    {"type": "emoji", "name": "wave"},
+                    {"type": "broadcast", "range": "channel"},
+                    {"type": "channel", "channel_id": "C1"},
+                    {"type": "usergroup", "usergroup_id": "S1"},
+                    {"type": "date", "timestamp": 1700000000, "fallback": "Nov 14"},
+                ])),
+                "<@U1> see <https://example.com|the docs> <https://example.com> \
+                 :wave:<!channel><#C1><!subteam^S1>Nov 14",
+            ),
+        ];
+        for (element, expected) in cases {
+            let message = json!({"blocks": [{"type": "rich_text", "elements": [element]}]});
+            assert_eq!(render(message.clone()), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn renders_blocks_over_text() {
+        let cases = [
+            (
+                json!({"text": "fallback", "blocks": [
+                    {"type": "header", "text": {"type": "plain_text", "text": "Deploy <prod>"}},
+                    {"type": "divider"},
+                    {"type": "section", "text": {"type": "mrkdwn", "text": "*done*"},
+                        "fields": [{"type": "plain_text", "text": "a & b"}]},
+                ]}),
+                "*Deploy &lt;prod&gt;*\n---\n*done*\na &amp; b",
+            ),
+            (
+                json!({"text": "pick one", "blocks": [{"type": "actions", "elements": [
+                    {"type": "button", "text": {"type": "plain_text", "text": "Ack"}},
+                ]}]}),
+                "pick one",
+            ),
+            (
+                json!({"text": "", "files": [
+                    {"id": "F1", "name": "a.png", "permalink": "https://example.com/f"},
+                    {"id": "F2"},
+                ]}),
+                "file: <https://example.com/f|a.png>\nfile: F2",
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(render(message.clone()), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn renders_attachments() {
+        let cases = [
+            (
+                json!({"fallback": "Build #12 failed"}),
+                "&gt; Build #12 failed",
+            ),
+            (
+                json!({
+                    "fallback": "not shown",
+                    "pretext": "heads up",
+                    "title": "Deploy",
+                    "title_link": "https://example.com",
+                    "text": "done\nok",
+                    "fields": [{"title": "Region", "value": "eu"}, {"value": "bare"}],
+                    "footer": "ci",
+                }),
+                "heads up\n&gt; *<https://example.com|Deploy>*\n&gt; done\n&gt; ok\n\
+                 &gt; *Region*: eu\n&gt; bare\n&gt; ci",
+            ),
+            (json!({"color": "#d00000"}), ""),
+        ];
+        for (attachment, expected) in cases {
+            let message = json!({"text": "", "attachments": [attachment]});
+            assert_eq!(render(message.clone()), expected, "{}", message);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use aes::Aes128;
 use block_modes::block_padding::NoPadding;
 use block_modes::{BlockMode, Cbc};
@@ -16,11 +17,7 @@ impl UnixCookieDecryptor {
         Self { rounds }
     }
 
-    pub fn decrypt(
-        &self,
-        mut value: Vec<u8>,
-        key: &[u8],
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    pub fn decrypt(&self, mut value: Vec<u8>, key: &[u8]) -> Result<Vec<u8>> {
         // Generate derived key using PBKDF2
         let derived_key: [u8; 16] = pbkdf2_hmac_array::<Sha1, 16>(key, b"saltysalt", self.rounds);
 
@@ -28,24 +25,29 @@ impl UnixCookieDecryptor {
         let iv: [u8; 16] = [b' '; 16];
 
         // Create cipher instance
-        let cipher = Aes128Cbc::new_from_slices(&derived_key, &iv)?;
+        let cipher = Aes128Cbc::new_from_slices(&derived_key, &iv)
+            .map_err(|e| Error::Auth(format!("invalid cookie key: {}", e)))?;
 
         // Decrypt the value
-        let decrypted = cipher.decrypt(&mut value)?;
+        let decrypted = cipher
+            .decrypt(&mut value)
+            .map_err(|e| Error::Auth(format!("could not decrypt cookie: {}", e)))?;
 
         // Get the number of padding bytes to remove from the last byte
-        let padding_len = *decrypted.last().ok_or("Empty decrypted data")? as usize;
+        let padding_len = *decrypted
+            .last()
+            .ok_or_else(|| Error::Auth("empty decrypted cookie".into()))? as usize;
 
         // Remove padding
         if padding_len > decrypted.len() {
-            return Err("Invalid padding length".into());
+            return Err(Error::Auth("invalid cookie padding length".into()));
         }
         let decrypted = &decrypted[..decrypted.len() - padding_len];
         let pattern = b"xoxd-";
         let index = decrypted
             .windows(pattern.len())
             .position(|window| window == pattern)
-            .unwrap();
+            .ok_or_else(|| Error::Auth("decrypted cookie is not a slack session".into()))?;
         Ok(decrypted[index..].to_vec())
     }
 }
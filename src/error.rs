@@ -0,0 +1,107 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Bad command line input, such as a link that can't be parsed.
+    Input(String),
+    /// No usable credentials: missing cookie, keyring entry or token.
+    Auth(String),
+    /// The request never got a response from Slack.
+    Transport(reqwest::Error),
+    /// Slack answered with HTTP 429.
+    RateLimited {
+        method: String,
+        retry_after: Option<u64>,
+    },
+    /// Slack answered `{"ok": false, "error": ...}`, with any extra
+    /// `response_metadata.messages` it sent along.
+    Api {
+        method: String,
+        error: String,
+        messages: Vec<String>,
+    },
+    /// Reading or writing the local sqlite cache failed.
+    Cache(String),
+    /// A response or value couldn't be decoded.
+    Parse(String),
+    /// Writing results to stdout failed.
+    Output(String),
+}
+
+impl Error {
+    /// Process exit code for this error, distinct per variant so scripts can
+    /// tell a bad link from an expired session.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Input(_) => 2,
+            Error::Auth(_) => 3,
+            Error::Transport(_) => 4,
+            Error::RateLimited { .. } => 5,
+            Error::Api { .. } => 6,
+            Error::Cache(_) => 7,
+            Error::Parse(_) => 8,
+            Error::Output(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Input(msg) => write!(f, "{}", msg),
+            Error::Auth(msg) => write!(f, "authentication failed: {}", msg),
+            Error::Transport(e) => write!(f, "could not reach slack: {}", e),
+            Error::RateLimited {
+                method,
+                retry_after: Some(secs),
+            } => write!(f, "{} is rate limited, retry after {}s", method, secs),
+            Error::RateLimited { method, .. } => write!(f, "{} is rate limited", method),
+            Error::Api {
+                method,
+                error,
+                messages,
+            } => {
+                write!(f, "{} failed: {}", method, error)?;
+                messages.iter().try_for_each(|m| write!(f, "\n  {}", m))
+            }
+            Error::Cache(msg) => write!(f, "cache error: {}", msg),
+            Error::Parse(msg) => write!(f, "could not parse response: {}", msg),
+            Error::Output(msg) => write!(f, "could not write output: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Transport(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<sqlite::Error> for Error {
+    fn from(e: sqlite::Error) -> Self {
+        Error::Cache(e.to_string())
+    }
+}
+
+impl From<keyring::Error> for Error {
+    fn from(e: keyring::Error) -> Self {
+        Error::Auth(e.to_string())
+    }
+}
@@ -0,0 +1,410 @@
+use crate::mrkdwn::{self, Token};
+use clap::ValueEnum;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// The document `export` writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Markup {
+    #[default]
+    Markdown,
+    /// A single html file with its styles inline
+    Html,
+}
+
+/// Which messages `export` fetches, named after the command that reads them.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Scope {
+    /// The thread the link points into
+    Thread,
+    /// The channel's messages, without replies
+    Read,
+    /// Every thread started in the channel, with its replies
+    ReadThreaded,
+}
+
+/// Messages ready to render, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub channel: String,
+    pub messages: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub author: String,
+    pub ts: String,
+    /// Slack mrkdwn, with `<@U…>` mentions already given their `|name`.
+    pub text: String,
+    pub permalink: String,
+    pub replies: Vec<Entry>,
+}
+
+const STYLE: &str = "body{font-family:-apple-system,'Segoe UI',Helvetica,Arial,sans-serif;max-width:50em;margin:2em auto;padding:0 1em;color:#1d1c1d;line-height:1.45}\
+header.transcript{border-bottom:1px solid #ddd;margin-bottom:1em}\
+.range{color:#616061}\
+.message{margin:1em 0}\
+.author{font-weight:bold}\
+.message time a{color:#616061;font-size:.85em;text-decoration:none;margin-left:.5em}\
+.text p{margin:.25em 0}\
+.replies{margin-left:1em;padding-left:1em;border-left:3px solid #ddd}\
+blockquote{margin:.25em 0;padding-left:.75em;border-left:3px solid #ccc;color:#454245}\
+code,pre{font-family:Menlo,Consolas,monospace;font-size:.9em;background:#f6f6f6;border-radius:3px}\
+code{padding:0 .2em}\
+pre{padding:.5em;white-space:pre-wrap}";
+
+impl Transcript {
+    /// Times of the oldest and newest message, replies included.
+    fn span(&self) -> Option<(String, String)> {
+        fn all(entries: &[Entry]) -> Vec<&Entry> {
+            entries
+                .iter()
+                .flat_map(|e| std::iter::once(e).chain(all(&e.replies)))
+                .collect()
+        }
+        let entries = all(&self.messages);
+        let first = entries
+            .iter()
+            .min_by(|a, b| crate::time::by_ts(&a.ts, &b.ts))?;
+        let last = entries
+            .iter()
+            .max_by(|a, b| crate::time::by_ts(&a.ts, &b.ts))?;
+        Some((
+            crate::time::display(&first.ts),
+            crate::time::display(&last.ts),
+        ))
+    }
+
+    pub fn render(&self, markup: Markup) -> String {
+        match markup {
+            Markup::Markdown => self.markdown(),
+            Markup::Html => self.html(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = format!("# #{}\n\n", self.channel);
+        if let Some((from, to)) = self.span() {
+            out.push_str(&format!("{} to {}\n\n", from, to));
+        }
+        for entry in &self.messages {
+            out.push_str("---\n\n");
+            out.push_str(&markdown_entry(entry));
+            out.push('\n');
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        let channel = escape(&self.channel);
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>#{}</title>\n<style>{}</style>\n</head>\n<body>\n<header class=\"transcript\">\n<h1>#{}</h1>\n",
+            channel, STYLE, channel
+        );
+        if let Some((from, to)) = self.span() {
+            out.push_str(&format!("<p class=\"range\">{} to {}</p>\n", from, to));
+        }
+        out.push_str("</header>\n");
+        for entry in &self.messages {
+            out.push_str(&html_entry(entry));
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Replies nest as quotes under their parent.
+fn markdown_entry(entry: &Entry) -> String {
+    let mut out = format!(
+        "**{}** · [{}]({})\n\n{}\n",
+        escape_markdown(&entry.author),
+        crate::time::display(&entry.ts),
+        entry.permalink,
+        convert(&entry.text, Markup::Markdown)
+    );
+    for reply in &entry.replies {
+        out.push('\n');
+        for line in markdown_entry(reply).lines() {
+            out.push_str(if line.is_empty() { ">" } else { "> " });
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn html_entry(entry: &Entry) -> String {
+    let mut out = format!(
+        "<article class=\"message\">\n<header><span class=\"author\">{}</span><time datetime=\"{}\"><a href=\"{}\">{}</a></time></header>\n<div class=\"text\">{}</div>\n",
+        escape(&entry.author),
+        crate::time::iso8601(&entry.ts),
+        escape(&entry.permalink),
+        crate::time::display(&entry.ts),
+        convert(&entry.text, Markup::Html)
+    );
+    if !entry.replies.is_empty() {
+        out.push_str("<section class=\"replies\">\n");
+        entry
+            .replies
+            .iter()
+            .for_each(|reply| out.push_str(&html_entry(reply)));
+        out.push_str("</section>\n");
+    }
+    out.push_str("</article>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Backslash-escapes what Markdown would read as emphasis, code, links,
+/// html or entities.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_~[]<>&".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Escapes what would open a heading, list or rule at the start of a
+/// Markdown line.
+fn escape_line_start(line: String) -> String {
+    static ORDERED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d+)([.)])").unwrap());
+    if line.starts_with(['#', '-', '+', '=']) {
+        return format!("\\{}", line);
+    }
+    ORDERED.replace(&line, "$1\\$2").into_owned()
+}
+
+/// Only web and mail links are kept, anything else such as `javascript:`
+/// shows as text.
+fn is_safe_link(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "mailto"))
+}
+
+/// Markdown link destinations end at a space or an unbalanced `)`.
+fn encode_url(url: &str) -> String {
+    url.replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+}
+
+/// A line of a message, or a code block, which is a block of its own.
+enum Line<'a> {
+    Text { quote: bool, tokens: &'a [Token] },
+    Code(&'a str),
+}
+
+/// Converts mrkdwn into `markup`: consecutive lines make a paragraph, or a
+/// quote when they are quoted, and blank lines and code blocks end one.
+fn convert(text: &str, markup: Markup) -> String {
+    let tokens = mrkdwn::tokenize(text);
+    let mut lines: Vec<Line> = Vec::new();
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if matches!(token, Token::Newline | Token::CodeBlock(_)) {
+            lines.push(line(&tokens[start..i]));
+            if let Token::CodeBlock(code) = token {
+                lines.push(Line::Code(code));
+            }
+            start = i + 1;
+        }
+    }
+    lines.push(line(&tokens[start..]));
+
+    let mut blocks: Vec<String> = Vec::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut quoted = false;
+    for line in lines {
+        match line {
+            Line::Text { quote, tokens } if !tokens.is_empty() => {
+                if quote != quoted {
+                    blocks.extend(paragraph_block(&mut paragraph, quoted, markup));
+                    quoted = quote;
+                }
+                paragraph.push(match markup {
+                    Markup::Markdown => escape_line_start(inline(tokens, markup)),
+                    Markup::Html => inline(tokens, markup),
+                });
+            }
+            Line::Text { .. } => blocks.extend(paragraph_block(&mut paragraph, quoted, markup)),
+            Line::Code(code) => {
+                blocks.extend(paragraph_block(&mut paragraph, quoted, markup));
+                blocks.push(match markup {
+                    Markup::Markdown => format!("```\n{}\n```", code),
+                    Markup::Html => format!("<pre><code>{}</code></pre>", escape(code)),
+                });
+            }
+        }
+    }
+    blocks.extend(paragraph_block(&mut paragraph, quoted, markup));
+    blocks.join("\n\n")
+}
+
+/// `tokenize` gives a quoted line as a single quote token.
+fn line(tokens: &[Token]) -> Line<'_> {
+    match tokens {
+        [Token::Quote(inner)] => Line::Text {
+            quote: true,
+            tokens: inner,
+        },
+        tokens => Line::Text {
+            quote: false,
+            tokens,
+        },
+    }
+}
+
+/// Takes the lines gathered so far as one paragraph, if there are any.
+fn paragraph_block(lines: &mut Vec<String>, quote: bool, markup: Markup) -> Option<String> {
+    if lines.is_empty() {
+        return None;
+    }
+    let lines = std::mem::take(lines);
+    Some(match (markup, quote) {
+        (Markup::Markdown, false) => lines.join("  \n"),
+        (Markup::Markdown, true) => lines
+            .iter()
+            .map(|line| format!("> {}", line))
+            .collect::<Vec<_>>()
+            .join("  \n"),
+        (Markup::Html, false) => format!("<p>{}</p>", lines.join("<br>\n")),
+        (Markup::Html, true) => format!("<blockquote>{}</blockquote>", lines.join("<br>\n")),
+    })
+}
+
+/// Inline markup: emphasis, code, mentions and links. Mentions show their
+/// label, which `entries` gives users, else their id.
+fn inline(tokens: &[Token], markup: Markup) -> String {
+    let html = markup == Markup::Html;
+    let wrap = |inner: String, markdown: &str, tag: &str| {
+        if html {
+            format!("<{}>{}</{}>", tag, inner, tag)
+        } else {
+            format!("{}{}{}", markdown, inner, markdown)
+        }
+    };
+    let mention = |sigil: &str, name: &str| {
+        // user group labels come with their @ already
+        let name = format!("{}{}", sigil, name.trim_start_matches(sigil));
+        if html {
+            format!("<strong>{}</strong>", escape(&name))
+        } else {
+            format!("**{}**", escape_markdown(&name))
+        }
+    };
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Text(text) if html => escape(text),
+            Token::Text(text) => escape_markdown(text),
+            Token::Bold(inner) => wrap(inline(inner, markup), "**", "strong"),
+            Token::Italic(inner) => wrap(inline(inner, markup), "_", "em"),
+            Token::Strike(inner) => wrap(inline(inner, markup), "~~", "del"),
+            Token::Quote(inner) => inline(inner, markup),
+            Token::Code(code) if html => format!("<code>{}</code>", escape(code)),
+            Token::Code(code) => format!("`{}`", code),
+            // lines are split on these before they get here
+            Token::CodeBlock(_) | Token::Newline => String::new(),
+            Token::User { id, label } | Token::Group { id, label } => {
+                mention("@", label.as_deref().unwrap_or(id))
+            }
+            Token::Channel { id, label } => mention("#", label.as_deref().unwrap_or(id)),
+            Token::Broadcast(name) => mention("@", name),
+            Token::Link { url, label } => {
+                let text = label.as_deref().unwrap_or(url);
+                match (html, is_safe_link(url)) {
+                    (true, true) => format!("<a href=\"{}\">{}</a>", escape(url), escape(text)),
+                    (true, false) => escape(text),
+                    (false, true) => format!("[{}]({})", escape_markdown(text), encode_url(url)),
+                    (false, false) => escape_markdown(text),
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_mrkdwn() {
+        let cases = [
+            (
+                "*bold* _it_ ~gone~ `x &amp;&amp; y`",
+                "**bold** _it_ ~~gone~~ `x && y`",
+                "<p><strong>bold</strong> <em>it</em> <del>gone</del> <code>x &amp;&amp; y</code></p>",
+            ),
+            (
+                "<@U0MOCK01|alice> in <#C0MOCK01> <!here> <!subteam^S0MOCK|@oncall>",
+                "**@alice** in **#C0MOCK01** **@here** **@oncall**",
+                "<p><strong>@alice</strong> in <strong>#C0MOCK01</strong> <strong>@here</strong> <strong>@oncall</strong></p>",
+            ),
+            (
+                "<!date^1392734382^{date_short}|Feb 18, 2014>",
+                "Feb 18, 2014",
+                "<p>Feb 18, 2014</p>",
+            ),
+            (
+                "<https://example.com/?a=1&amp;b=2|the docs>",
+                "[the docs](https://example.com/?a=1&b=2)",
+                "<p><a href=\"https://example.com/?a=1&amp;b=2\">the docs</a></p>",
+            ),
+            (
+                "2*3*4 snake_case [x](y) &lt;b&gt;hi&lt;/b&gt; &amp;amp; `",
+                "2\\*3\\*4 snake\\_case \\[x\\](y) \\<b\\>hi\\</b\\> \\&amp; \\`",
+                "<p>2*3*4 snake_case [x](y) &lt;b&gt;hi&lt;/b&gt; &amp;amp; `</p>",
+            ),
+            (
+                "# not a heading\n1. not a list",
+                "\\# not a heading  \n1\\. not a list",
+                "<p># not a heading<br>\n1. not a list</p>",
+            ),
+            (
+                "<https://en.wikipedia.org/wiki/Rust_(lang)|Rust [lang]>",
+                "[Rust \\[lang\\]](https://en.wikipedia.org/wiki/Rust_%28lang%29)",
+                "<p><a href=\"https://en.wikipedia.org/wiki/Rust_(lang)\">Rust [lang]</a></p>",
+            ),
+            (
+                "<mailto:bob@example.com|bob> <javascript:alert(1)|click>",
+                "[bob](mailto:bob@example.com) click",
+                "<p><a href=\"mailto:bob@example.com\">bob</a> click</p>",
+            ),
+            (
+                "one\ntwo\n\nthree",
+                "one  \ntwo\n\nthree",
+                "<p>one<br>\ntwo</p>\n\n<p>three</p>",
+            ),
+            (
+                "&gt; quoted\nreply",
+                "> quoted\n\nreply",
+                "<blockquote>quoted</blockquote>\n\n<p>reply</p>",
+            ),
+            (
+                "said\n&gt;&gt;&gt; all\nof this",
+                "said\n\n> all  \n> of this",
+                "<p>said</p>\n\n<blockquote>all<br>\nof this</blockquote>",
+            ),
+            (
+                "run\n```\nmake &lt;all&gt;\n```\ndone",
+                "run\n\n```\nmake <all>\n```\n\ndone",
+                "<p>run</p>\n\n<pre><code>make &lt;all&gt;</code></pre>\n\n<p>done</p>",
+            ),
+        ];
+        for (input, markdown, html) in cases {
+            assert_eq!(convert(input, Markup::Markdown), markdown, "{:?}", input);
+            assert_eq!(convert(input, Markup::Html), html, "{:?}", input);
+        }
+    }
+}
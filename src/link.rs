@@ -0,0 +1,324 @@
+use crate::error::{Error, Result};
+use url::Url;
+
+const EXPECTED: &str = "expected a message link like https://<team>.slack.com/archives/<channel>/p<ts>, \
+     https://app.slack.com/client/<team id>/<channel> or slack://channel?team=<team id>&id=<channel>";
+
+/// Where a Slack link points: a channel, and maybe a message in it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Link {
+    /// Workspace subdomain, e.g. `acme` for acme.slack.com. Client and deep
+    /// links only carry the team id.
+    pub team: Option<String>,
+    pub team_id: Option<String>,
+    pub channel: String,
+    /// The linked message, as a Slack timestamp.
+    pub ts: Option<String>,
+    /// Parent of the linked message when it is a thread reply.
+    pub thread_ts: Option<String>,
+}
+
+impl Link {
+    /// The thread the link belongs to, which is the message itself unless it is a reply.
+    pub fn thread(&self) -> Option<&str> {
+        self.thread_ts.as_deref().or(self.ts.as_deref())
+    }
+
+    /// The team to talk to: `flag` when given, else the one in the link.
+    pub fn team(&self, flag: Option<&str>) -> Result<String> {
+        flag.map(str::to_string)
+            .or_else(|| self.team.clone())
+            .ok_or_else(|| {
+                Error::Input(format!(
+                    "the link only names team {}, pass its name with --team",
+                    self.team_id.as_deref().unwrap_or("by id")
+                ))
+            })
+    }
+}
+
+/// The web link to a message, the archive form `parse` reads back.
+pub fn permalink(team: &str, channel: &str, ts: &str, thread_ts: Option<&str>) -> String {
+    let mut link = format!(
+        "https://{}.slack.com/archives/{}/p{}",
+        team,
+        channel,
+        ts.replace('.', "")
+    );
+    if let Some(thread_ts) = thread_ts.filter(|thread_ts| *thread_ts != ts) {
+        link.push_str(&format!("?thread_ts={}&cid={}", thread_ts, channel));
+    }
+    link
+}
+
+/// Parses any of the link shapes Slack hands out for a channel or message.
+pub fn parse(arg: &str) -> Result<Link> {
+    let invalid = |reason: String| Error::Input(format!("{} is not a slack link: {}", arg, reason));
+    let url = Url::parse(arg.trim()).map_err(|_| invalid(EXPECTED.to_string()))?;
+    let link = match url.scheme() {
+        "slack" => parse_deep_link(&url),
+        "http" | "https" => parse_web_link(&url),
+        scheme => Err(format!("unsupported scheme {}:, {}", scheme, EXPECTED)),
+    }
+    .map_err(invalid)?;
+    check_channel(&link.channel).map_err(invalid)?;
+    Ok(link)
+}
+
+/// `https://acme.slack.com/archives/C123/p1700000000123456?thread_ts=...&cid=C123`
+/// or `https://app.slack.com/client/T123/C123[/thread/C123-1700000000.123456]`
+fn parse_web_link(url: &Url) -> std::result::Result<Link, String> {
+    let host = url.host_str().unwrap_or_default();
+    let subdomain = host
+        .strip_suffix(".slack.com")
+        .ok_or_else(|| format!("{} is not a slack.com address", host))?;
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let mut link = match segments.as_slice() {
+        ["client", team_id, channel, rest @ ..] => Link {
+            team_id: Some(team_id.to_string()),
+            channel: channel.to_string(),
+            ts: match rest {
+                [] => None,
+                ["thread", thread] => {
+                    let ts = thread
+                        .split_once('-')
+                        .map(|(_, ts)| ts)
+                        .filter(|ts| is_ts(ts))
+                        .ok_or_else(|| format!("{} is not a thread", thread))?;
+                    Some(ts.to_string())
+                }
+                _ => return Err(format!("unexpected path {}", url.path())),
+            },
+            ..Link::default()
+        },
+        ["archives", channel, rest @ ..] => Link {
+            // enterprise grid workspaces live at acme.enterprise.slack.com
+            team: subdomain.split('.').next().map(str::to_string),
+            channel: channel.to_string(),
+            ts: match rest {
+                [] => None,
+                [message] => Some(permalink_ts(message).ok_or_else(|| {
+                    format!("{} is not a message id like p1700000000123456", message)
+                })?),
+                _ => return Err(format!("unexpected path {}", url.path())),
+            },
+            ..Link::default()
+        },
+        _ => return Err(format!("no channel in {}, {}", url.path(), EXPECTED)),
+    };
+    if let Some((_, thread_ts)) = url.query_pairs().find(|(k, _)| k == "thread_ts") {
+        if !is_ts(&thread_ts) {
+            return Err(format!("thread_ts {} is not a slack timestamp", thread_ts));
+        }
+        link.thread_ts = Some(thread_ts.into_owned());
+    }
+    Ok(link)
+}
+
+/// `slack://channel?team=T123&id=C123`
+fn parse_deep_link(url: &Url) -> std::result::Result<Link, String> {
+    if url.host_str() != Some("channel") {
+        return Err(format!(
+            "slack://{} links don't point at a channel",
+            url.host_str().unwrap_or_default()
+        ));
+    }
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    Ok(Link {
+        team_id: param("team"),
+        channel: param("id").ok_or("no channel id= in the link")?,
+        ..Link::default()
+    })
+}
+
+fn check_channel(channel: &str) -> std::result::Result<(), String> {
+    if channel.is_empty() || !channel.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("{:?} is not a channel id", channel));
+    }
+    Ok(())
+}
+
+/// Permalinks drop the dot: `p1700000000123456` is ts `1700000000.123456`.
+fn permalink_ts(segment: &str) -> Option<String> {
+    let digits = segment.strip_prefix('p')?;
+    if digits.len() <= 6 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (seconds, micros) = digits.split_at(digits.len() - 6);
+    Some(format!("{}.{}", seconds, micros))
+}
+
+fn is_ts(value: &str) -> bool {
+    match value.split_once('.') {
+        Some((seconds, micros)) => {
+            !seconds.is_empty()
+                && !micros.is_empty()
+                && seconds
+                    .chars()
+                    .chain(micros.chars())
+                    .all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(team: Option<&str>, team_id: Option<&str>, channel: &str, ts: Option<&str>) -> Link {
+        Link {
+            team: team.map(str::to_string),
+            team_id: team_id.map(str::to_string),
+            channel: channel.to_string(),
+            ts: ts.map(str::to_string),
+            thread_ts: None,
+        }
+    }
+
+    #[test]
+    fn parses_every_link_shape() {
+        let cases = [
+            (
+                "https://acme.slack.com/archives/C0MOCK01/p1700000000123456",
+                link(Some("acme"), None, "C0MOCK01", Some("1700000000.123456")),
+            ),
+            (
+                "https://acme.slack.com/archives/C0MOCK01",
+                link(Some("acme"), None, "C0MOCK01", None),
+            ),
+            (
+                "https://acme.slack.com/archives/C0MOCK01/",
+                link(Some("acme"), None, "C0MOCK01", None),
+            ),
+            (
+                "https://acme.enterprise.slack.com/archives/C0MOCK01/p1700000000123456",
+                link(Some("acme"), None, "C0MOCK01", Some("1700000000.123456")),
+            ),
+            (
+                "https://app.slack.com/client/T0MOCK/C0MOCK01",
+                link(None, Some("T0MOCK"), "C0MOCK01", None),
+            ),
+            (
+                "https://app.slack.com/client/T0MOCK/C0MOCK01/thread/C0MOCK01-1700000000.000100",
+                link(None, Some("T0MOCK"), "C0MOCK01", Some("1700000000.000100")),
+            ),
+            (
+                "slack://channel?team=T0MOCK&id=C0MOCK01",
+                link(None, Some("T0MOCK"), "C0MOCK01", None),
+            ),
+            (
+                "  https://acme.slack.com/archives/D084TR3F18X/p1700000000000100\n",
+                link(Some("acme"), None, "D084TR3F18X", Some("1700000000.000100")),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn reply_links_keep_their_thread() {
+        let link = parse(
+            "https://acme.slack.com/archives/C0MOCK01/p1700000120000300?thread_ts=1700000000.000100&cid=C0MOCK01",
+        )
+        .unwrap();
+        assert_eq!(link.ts.as_deref(), Some("1700000120.000300"));
+        assert_eq!(link.thread_ts.as_deref(), Some("1700000000.000100"));
+        assert_eq!(link.thread(), Some("1700000000.000100"));
+
+        let root = parse("https://acme.slack.com/archives/C0MOCK01/p1700000000000100").unwrap();
+        assert_eq!(root.thread(), Some("1700000000.000100"));
+    }
+
+    #[test]
+    fn permalinks_parse_back() {
+        let root = permalink(
+            "acme",
+            "C0MOCK01",
+            "1700000000.000100",
+            Some("1700000000.000100"),
+        );
+        assert_eq!(
+            root,
+            "https://acme.slack.com/archives/C0MOCK01/p1700000000000100"
+        );
+        let reply = permalink(
+            "acme",
+            "C0MOCK01",
+            "1700000120.000300",
+            Some("1700000000.000100"),
+        );
+        let parsed = parse(&reply).unwrap();
+        assert_eq!(parsed.ts.as_deref(), Some("1700000120.000300"));
+        assert_eq!(parsed.thread(), Some("1700000000.000100"));
+    }
+
+    #[test]
+    fn team_comes_from_the_flag_or_the_link() {
+        let archive = parse("https://acme.slack.com/archives/C0MOCK01").unwrap();
+        assert_eq!(archive.team(None).unwrap(), "acme");
+        assert_eq!(archive.team(Some("other")).unwrap(), "other");
+
+        let client = parse("https://app.slack.com/client/T0MOCK/C0MOCK01").unwrap();
+        assert_eq!(client.team(Some("acme")).unwrap(), "acme");
+        let err = client.team(None).unwrap_err().to_string();
+        assert!(err.contains("T0MOCK") && err.contains("--team"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_other_links_with_a_reason() {
+        let cases = [
+            ("not a url", "expected a message link"),
+            (
+                "ftp://acme.slack.com/archives/C0MOCK01",
+                "unsupported scheme",
+            ),
+            (
+                "https://example.com/archives/C0MOCK01",
+                "not a slack.com address",
+            ),
+            ("https://acme.slack.com/", "no channel"),
+            ("https://acme.slack.com/messages/C0MOCK01", "no channel"),
+            (
+                "https://acme.slack.com/archives/C0MOCK01/p1",
+                "not a message id",
+            ),
+            (
+                "https://acme.slack.com/archives/C0MOCK01/1700000000123456",
+                "not a message id",
+            ),
+            (
+                "https://acme.slack.com/archives/C0MOCK01/p1700000000123456/extra",
+                "unexpected path",
+            ),
+            (
+                "https://acme.slack.com/archives/C0MOCK01?thread_ts=abc",
+                "not a slack timestamp",
+            ),
+            (
+                "https://acme.slack.com/archives/general-chat",
+                "not a channel id",
+            ),
+            (
+                "https://app.slack.com/client/T0MOCK/C0MOCK01/thread/nope",
+                "not a thread",
+            ),
+            ("slack://open?team=T0MOCK", "don't point at a channel"),
+            ("slack://channel?team=T0MOCK", "no channel id"),
+        ];
+        for (input, reason) in cases {
+            let err = parse(input).unwrap_err();
+            assert_eq!(err.exit_code(), 2, "{}", input);
+            let message = err.to_string();
+            assert!(message.contains(reason), "{}: {}", input, message);
+        }
+    }
+}
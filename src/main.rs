@@ -1,35 +1,54 @@
 mod cache;
 mod cli;
+mod content;
 mod decryptor;
+mod error;
+mod export;
+mod link;
 mod model;
+mod mrkdwn;
+mod output;
+mod ratelimit;
 mod slack;
+mod time;
+mod transport;
+mod vcr;
 
 use cache::sqlite::InMemoryCache as db;
 use clap::Parser;
 use cli::{Cli, SubCommand};
-use std::{error::Error, ops::Sub};
+use colored::Colorize;
+use error::{Error, Result};
+use export::Scope;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use transport::{HttpTransport, Transport};
 
-fn parse_url(arg: &str) -> Result<(String, String, String), Box<dyn std::error::Error>> {
-    let url = url::Url::parse(arg)?;
-    let team = url
-        .host_str()
-        .map(|x| x.split('.'))
-        .and_then(|mut x| x.next())
-        .unwrap()
-        .to_string();
-
-    let mut path_segments = url.path_segments().expect("no path segments in URL");
-    let channel = path_segments.nth(1).expect("no channel").to_string();
+/// A channel named on the command line is resolved once the cache is open.
+enum ChannelArg {
+    Id(String),
+    Name(String),
+}
 
-    let start_time = path_segments
-        .nth(0)
-        .and_then(|x| x.strip_prefix("p"))
-        .map(|x| x.to_string())
-        .unwrap();
-    Ok((team, channel, start_time))
+/// Reads `read`'s target: a message link, or `#name` within `team`.
+fn parse_channel_arg(
+    arg: &str,
+    team: Option<&str>,
+) -> Result<(String, ChannelArg, Option<String>)> {
+    match arg.strip_prefix('#') {
+        Some(name) => {
+            let team =
+                team.ok_or_else(|| Error::Input(format!("--team is needed to find #{}", name)))?;
+            Ok((team.to_string(), ChannelArg::Name(name.to_string()), None))
+        }
+        None => {
+            let link = link::parse(arg)?;
+            Ok((link.team(team)?, ChannelArg::Id(link.channel), link.ts))
+        }
+    }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
     let cli = Cli::parse();
     let mut log_filter_level = log::LevelFilter::Warn;
     if cli.verbose {
@@ -41,21 +60,142 @@ fn main() -> Result<(), Box<dyn Error>> {
     env_logger::Builder::new()
         .filter_level(log_filter_level)
         .init();
-    let db = db::new()?;
+    // colored already drops colors when stdout isn't a terminal, records never get them
+    if cli.format != output::Format::Text {
+        colored::control::set_override(false);
+    }
+    if let Err(e) = run(cli) {
+        eprintln!("{} {}", "error:".red().bold(), e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let db = db::new(&cache::sqlite::db_path(cli.db.as_deref())?)?;
+    let transport: Box<dyn Transport> = match (&cli.record, &cli.replay) {
+        (_, Some(dir)) => Box::new(vcr::ReplayTransport::new(dir)?),
+        (Some(dir), None) => Box::new(vcr::RecordingTransport::new(
+            Box::new(HttpTransport::new()),
+            dir,
+        )?),
+        (None, None) => Box::new(HttpTransport::new()),
+    };
+    let options = slack::Options {
+        token_file: cli.token_file.as_deref(),
+        base_url: slack::base_url(cli.api_url.as_deref())?,
+        offline: cli.replay.is_some(),
+        format: cli.format,
+        time_format: cli.time_format,
+        tz: &cli.tz,
+        date_headers: cli.date_headers,
+    };
 
     match cli.subcmd {
-        SubCommand::Read { ref arg, .. } | SubCommand::ReadThreaded { ref arg, .. }| SubCommand::Thread { ref arg, .. } => {
-            let (team, channel, start_time) = parse_url(arg)?;
-            let mut slack_client = slack::new(team.as_ref(), db)?;
-            match cli.subcmd {
-                SubCommand::Read { count, .. } => {
-                    slack_client.read(&channel, &start_time, count)?
+        SubCommand::Read {
+            ref arg,
+            ref team,
+            ref since,
+            ref until,
+            count,
+            limit,
+        }
+        | SubCommand::ReadThreaded {
+            ref arg,
+            ref team,
+            ref since,
+            ref until,
+            count,
+            limit,
+            ..
+        } => {
+            let (team, channel, start_time) = parse_channel_arg(arg, team.as_deref())?;
+            let range = slack::Range {
+                oldest: since
+                    .as_deref()
+                    .map(time::parse_time)
+                    .transpose()?
+                    .or(start_time),
+                latest: until.as_deref().map(time::parse_time).transpose()?,
+            };
+            let mut slack_client = slack::new(team.as_ref(), db, transport, options)?;
+            let channel = match channel {
+                ChannelArg::Id(id) => id,
+                ChannelArg::Name(name) => slack_client.channel_id(&name)?,
+            };
+            if let SubCommand::ReadThreaded { concurrency, .. } = cli.subcmd {
+                slack_client.read_threaded(&channel, &range, count, limit, concurrency)?
+            } else {
+                slack_client.read(&channel, &range, count, limit)?
+            }
+        }
+        SubCommand::Thread { arg, team, count } => {
+            let link = link::parse(&arg)?;
+            let ts = link
+                .thread()
+                .ok_or_else(|| Error::Input(format!("{} does not point at a message", arg)))?;
+            let team = link.team(team.as_deref())?;
+            let mut slack_client = slack::new(team.as_ref(), db, transport, options)?;
+            slack_client.thread(&link.channel, ts, count.map(NonZeroUsize::get))?
+        }
+        SubCommand::Export {
+            arg,
+            scope,
+            to,
+            output,
+            count,
+            limit,
+            team,
+            since,
+            until,
+            concurrency,
+        } => {
+            let (team, channel, start_time) = parse_channel_arg(&arg, team.as_deref())?;
+            // a reply's link starts the read at the reply but exports its whole thread
+            let thread = match channel {
+                ChannelArg::Id(_) => link::parse(&arg)?.thread().map(str::to_string),
+                ChannelArg::Name(_) => None,
+            };
+            let range = slack::Range {
+                oldest: since
+                    .as_deref()
+                    .map(time::parse_time)
+                    .transpose()?
+                    .or(start_time),
+                latest: until.as_deref().map(time::parse_time).transpose()?,
+            };
+            let scope = scope.unwrap_or(match thread {
+                Some(_) if since.is_none() && until.is_none() => Scope::Thread,
+                _ => Scope::Read,
+            });
+            let mut slack_client = slack::new(team.as_ref(), db, transport, options)?;
+            let channel = match channel {
+                ChannelArg::Id(id) => id,
+                ChannelArg::Name(name) => slack_client.channel_id(&name)?,
+            };
+            let threads = match scope {
+                Scope::Thread => {
+                    let ts = thread.ok_or_else(|| {
+                        Error::Input(format!("{} does not point at a thread to export", arg))
+                    })?;
+                    vec![slack_client.replies(&channel, &ts, None)?]
                 }
-                SubCommand::ReadThreaded { count, .. } => {
-                    slack_client.read_threaded(&channel, &start_time, count)?
+                Scope::Read => slack_client
+                    .messages(&channel, &range, count, limit)?
+                    .into_iter()
+                    .map(|message| vec![message])
+                    .collect(),
+                Scope::ReadThreaded => {
+                    slack_client.threads(&channel, &range, count, limit, concurrency)?
                 }
-                SubCommand::Thread { .. } => slack_client.thread(&channel, &start_time)?,
-                _ => {}
+            };
+            let document = slack_client.transcript(&channel, &threads).render(to);
+            match output {
+                Some(path) => std::fs::write(&path, document).map_err(|e| {
+                    Error::Output(format!("could not write {}: {}", path.display(), e))
+                })?,
+                None => std::io::stdout()
+                    .write_all(document.as_bytes())
+                    .map_err(|e| Error::Output(e.to_string()))?,
             }
         }
         SubCommand::Search {
@@ -63,16 +203,16 @@ fn main() -> Result<(), Box<dyn Error>> {
             team,
             count,
         } => {
-            let mut slack_client = slack::new(team.as_ref(), db)?;
-            slack_client.search(&keyword, count);
+            let mut slack_client = slack::new(team.as_ref(), db, transport, options)?;
+            slack_client.search(&keyword, count)?;
         }
         SubCommand::Sync { team } => {
-            let slack_client = slack::new(team.as_ref(), db)?;
-            slack_client.sync();
+            let slack_client = slack::new(team.as_ref(), db, transport, options)?;
+            slack_client.sync()?;
         },
         SubCommand::Send{team, msg}=> { 
-            let slack_client = slack::new(team.as_ref(), db)?;
-            slack_client.send(msg);
+            let slack_client = slack::new(team.as_ref(), db, transport, options)?;
+            slack_client.send(msg)?;
          }
     }
 
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Root {
+    #[serde(default)]
+    pub ok: bool,
+    #[serde(default)]
+    pub error: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub team: String,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default, rename = "team_id")]
+    pub team_id: String,
+    #[serde(default, rename = "user_id")]
+    pub user_id: String,
+}
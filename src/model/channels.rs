@@ -54,6 +54,8 @@ pub struct Channel {
     #[serde(default, rename = "is_mpim")]
     pub is_mpim: bool,
     #[serde(default)]
+    pub user: String,
+    #[serde(default)]
     pub updated: i64,
     #[serde(default)]
     pub topic: Topic,
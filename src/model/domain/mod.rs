@@ -5,6 +5,8 @@ pub struct User {
     pub id: String,
     pub name: String,
     pub email: String,
+    /// IANA timezone from the user's profile, e.g. `Europe/London`.
+    pub tz: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// Fields every Slack Web API response carries, read before the method
+/// specific body so a failed call is never mistaken for an empty result.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    #[serde(default)]
+    pub ok: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub warning: Option<String>,
+    #[serde(default, rename = "response_metadata")]
+    pub response_metadata: ResponseMetadata,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseMetadata {
+    #[serde(default)]
+    pub messages: Vec<String>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,4 +18,153 @@ pub struct Message {
     pub team: Option<String>,
     #[serde(rename = "thread_ts")]
     pub thread_ts: Option<String>,
+    /// `bot_message`, `channel_join`, `thread_broadcast` and so on. Plain
+    /// messages have none.
+    #[serde(default)]
+    pub subtype: Option<String>,
+    #[serde(rename = "bot_id", default)]
+    pub bot_id: Option<String>,
+    /// Name a bot or integration posted under, when there is no `user`.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub edited: Option<Edited>,
+    /// Set on thread parents only.
+    #[serde(rename = "reply_count", default)]
+    pub reply_count: Option<u32>,
+    #[serde(rename = "latest_reply", default)]
+    pub latest_reply: Option<String>,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub files: Vec<File>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Edited {
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub ts: String,
+}
+
+/// A Block Kit block. Only the parts that carry text are kept.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    #[serde(rename = "type", default)]
+    pub type_field: String,
+    /// Text of `section` and `header` blocks.
+    #[serde(default)]
+    pub text: Option<TextObject>,
+    #[serde(default)]
+    pub fields: Vec<TextObject>,
+    /// Parts of `context`, `rich_text` and `actions` blocks.
+    #[serde(default)]
+    pub elements: Vec<Element>,
+    #[serde(rename = "image_url", default)]
+    pub image_url: Option<String>,
+    #[serde(rename = "alt_text", default)]
+    pub alt_text: Option<String>,
+}
+
+/// `{"type": "mrkdwn" | "plain_text", "text": ...}`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextObject {
+    #[serde(rename = "type", default)]
+    pub type_field: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+/// An element of a block, or of a rich text element, which nest.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Element {
+    #[serde(rename = "type", default)]
+    pub type_field: String,
+    /// A string in rich text and context blocks, a text object on buttons.
+    #[serde(default)]
+    pub text: Option<Value>,
+    #[serde(default)]
+    pub elements: Vec<Element>,
+    /// `{"bold": true, ...}` on rich text, `bullet` or `ordered` on lists.
+    #[serde(default)]
+    pub style: Option<Value>,
+    #[serde(default)]
+    pub indent: usize,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(rename = "user_id", default)]
+    pub user_id: Option<String>,
+    #[serde(rename = "channel_id", default)]
+    pub channel_id: Option<String>,
+    #[serde(rename = "usergroup_id", default)]
+    pub usergroup_id: Option<String>,
+    /// `here`, `channel` or `everyone` on broadcasts.
+    #[serde(default)]
+    pub range: Option<String>,
+    /// Emoji name, without colons.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(rename = "alt_text", default)]
+    pub alt_text: Option<String>,
+    #[serde(default)]
+    pub fallback: Option<String>,
+}
+
+/// A legacy message attachment, as alerting integrations still send them.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    #[serde(default)]
+    pub fallback: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(default)]
+    pub pretext: String,
+    #[serde(rename = "author_name", default)]
+    pub author_name: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(rename = "title_link", default)]
+    pub title_link: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    #[serde(default)]
+    pub footer: String,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Field {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub short: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct File {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub mimetype: String,
+    #[serde(default)]
+    pub permalink: String,
 }
@@ -1,6 +1,8 @@
+pub mod auth;
 pub mod channels;
 pub mod conversations;
 pub mod domain;
+pub mod envelope;
 pub mod message;
 pub mod replies;
 pub mod search;
@@ -1,3 +1,4 @@
+use super::conversations::ResponseMetadata;
 use super::message::Message;
 use serde::{Deserialize, Serialize};
 
@@ -6,6 +7,11 @@ use serde::{Deserialize, Serialize};
 pub struct Root {
     pub ok: bool,
     pub messages: Vec<Message>,
+    /// Whether the thread goes on past `messages`.
     #[serde(rename = "has_more")]
+    #[serde(default)]
     pub has_more: bool,
+    #[serde(rename = "response_metadata")]
+    #[serde(default)]
+    pub response_metadata: ResponseMetadata,
 }
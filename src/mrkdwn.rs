@@ -0,0 +1,507 @@
+use colored::Colorize;
+
+/// A piece of Slack mrkdwn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// Plain text with `&amp;`, `&lt;` and `&gt;` decoded.
+    Text(String),
+    Bold(Vec<Token>),
+    Italic(Vec<Token>),
+    Strike(Vec<Token>),
+    Code(String),
+    CodeBlock(String),
+    /// One `&gt;` quoted line.
+    Quote(Vec<Token>),
+    Newline,
+    /// `<@U123>`, with the name older messages carry after a `|`.
+    User {
+        id: String,
+        label: Option<String>,
+    },
+    /// `<#C123|name>`
+    Channel {
+        id: String,
+        label: Option<String>,
+    },
+    /// A user group, `<!subteam^S123|@oncall>`.
+    Group {
+        id: String,
+        label: Option<String>,
+    },
+    /// `<!here>`, `<!channel>` or `<!everyone>`, without the `!`.
+    Broadcast(String),
+    Link {
+        url: String,
+        label: Option<String>,
+    },
+}
+
+/// How `render` prints tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Colored for a terminal
+    Styled,
+    /// Bare text, with quotes marked by `> `
+    Plain,
+}
+
+/// Looks up the names mentions point at.
+pub trait Names {
+    fn user(&mut self, id: &str) -> String;
+    fn channel(&mut self, id: &str) -> String;
+}
+
+/// Renders a message's mrkdwn for the terminal, collapsing blank lines.
+pub fn render(text: &str, mode: Mode, names: &mut impl Names) -> String {
+    render_tokens(&tokenize(text), mode, names)
+}
+
+/// Splits mrkdwn into tokens. Anything that doesn't parse as markup stays text.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut quote_rest = false;
+    let mut rest = text;
+    // code blocks go first, they may span lines and hold any markup
+    loop {
+        let block = rest.find("```").and_then(|start| {
+            rest[start + 3..]
+                .find("```")
+                .map(|len| (start, start + 3 + len))
+        });
+        let Some((start, end)) = block else {
+            lines(rest, &mut tokens, &mut quote_rest);
+            return tokens;
+        };
+        lines(&rest[..start], &mut tokens, &mut quote_rest);
+        let code = rest[start + 3..end].trim_matches('\n');
+        tokens.push(Token::CodeBlock(unescape(code)));
+        rest = &rest[end + 3..];
+    }
+}
+
+/// `&gt;` quotes a line, `&gt;&gt;&gt;` everything after it.
+fn lines(text: &str, tokens: &mut Vec<Token>, quote_rest: &mut bool) {
+    for (n, line) in text.split('\n').enumerate() {
+        if n > 0 {
+            tokens.push(Token::Newline);
+        }
+        if let Some(quoted) = line.strip_prefix("&gt;&gt;&gt;") {
+            *quote_rest = true;
+            tokens.push(Token::Quote(inline(quoted.trim_start())));
+        } else if *quote_rest {
+            tokens.push(Token::Quote(inline(line)));
+        } else if let Some(quoted) = line.strip_prefix("&gt;") {
+            tokens.push(Token::Quote(inline(
+                quoted.strip_prefix(' ').unwrap_or(quoted),
+            )));
+        } else if !line.is_empty() {
+            tokens.extend(inline(line));
+        }
+    }
+}
+
+fn inline(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while let Some(c) = line[i..].chars().next() {
+        let rest = &line[i..];
+        let parsed = match c {
+            '<' => angle(rest),
+            '`' => code(rest),
+            // markers only open at the start of a word, so snake_case stays text
+            '*' | '_' | '~' if !line[..i].chars().last().is_some_and(char::is_alphanumeric) => {
+                emphasis(rest, c)
+            }
+            _ => None,
+        };
+        match parsed {
+            Some((token, len)) => {
+                if !text.is_empty() {
+                    tokens.push(Token::Text(unescape(&std::mem::take(&mut text))));
+                }
+                tokens.push(token);
+                i += len;
+            }
+            None => {
+                text.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(unescape(&text)));
+    }
+    tokens
+}
+
+/// `<...>`: mentions, broadcasts, dates and links. Returns the token and
+/// how much of `rest` it used.
+fn angle(rest: &str) -> Option<(Token, usize)> {
+    let end = rest.find('>')?;
+    let inner = &rest[1..end];
+    if inner.is_empty() || inner.contains(['<', '\n']) {
+        return None;
+    }
+    let (target, label) = match inner.split_once('|') {
+        Some((target, label)) => (target, Some(unescape(label)).filter(|l| !l.is_empty())),
+        None => (inner, None),
+    };
+    let token = if let Some(id) = target.strip_prefix('@') {
+        Token::User {
+            id: id.to_string(),
+            label,
+        }
+    } else if let Some(id) = target.strip_prefix('#') {
+        Token::Channel {
+            id: id.to_string(),
+            label,
+        }
+    } else if let Some(command) = target.strip_prefix('!') {
+        let mut parts = command.split('^');
+        match parts.next().unwrap_or_default() {
+            "subteam" => Token::Group {
+                id: parts.next().unwrap_or_default().to_string(),
+                label,
+            },
+            // <!date^1392734382^{date_short}|Feb 18, 2014>, the label is the readable fallback
+            "date" => Token::Text(label.unwrap_or_else(|| unescape(command))),
+            name => Token::Broadcast(name.to_string()),
+        }
+    } else {
+        Token::Link {
+            url: unescape(target),
+            label,
+        }
+    };
+    Some((token, end + 1))
+}
+
+fn code(rest: &str) -> Option<(Token, usize)> {
+    let end = rest[1..].find('`')? + 1;
+    let inner = &rest[1..end];
+    if inner.is_empty() || inner.contains('\n') {
+        return None;
+    }
+    Some((Token::Code(unescape(inner)), end + 1))
+}
+
+/// `*bold*`, `_italic_` or `~strike~`, which can't start or end on a space
+/// and must close at the end of a word.
+fn emphasis(rest: &str, mark: char) -> Option<(Token, usize)> {
+    if rest[1..].chars().next().is_none_or(char::is_whitespace) {
+        return None;
+    }
+    for (end, c) in rest.char_indices().skip(2) {
+        if c == '\n' {
+            return None;
+        }
+        let closes = c == mark
+            && !rest[..end].ends_with(char::is_whitespace)
+            && !rest[end + 1..].starts_with(char::is_alphanumeric);
+        if closes {
+            let inner = inline(&rest[1..end]);
+            let token = match mark {
+                '*' => Token::Bold(inner),
+                '_' => Token::Italic(inner),
+                _ => Token::Strike(inner),
+            };
+            return Some((token, end + 1));
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn render_tokens(tokens: &[Token], mode: Mode, names: &mut impl Names) -> String {
+    let styled = mode == Mode::Styled;
+    let mut out = String::new();
+    for token in tokens {
+        match token {
+            Token::Text(text) => out.push_str(text),
+            Token::Bold(inner) => {
+                let inner = render_tokens(inner, mode, names);
+                out.push_str(&if styled {
+                    inner.bold().to_string()
+                } else {
+                    inner
+                });
+            }
+            Token::Italic(inner) => {
+                let inner = render_tokens(inner, mode, names);
+                out.push_str(&if styled {
+                    inner.italic().to_string()
+                } else {
+                    inner
+                });
+            }
+            Token::Strike(inner) => {
+                let inner = render_tokens(inner, mode, names);
+                out.push_str(&if styled {
+                    inner.strikethrough().to_string()
+                } else {
+                    inner
+                });
+            }
+            Token::Code(code) => {
+                out.push_str(&if styled {
+                    code.cyan().to_string()
+                } else {
+                    code.clone()
+                });
+            }
+            Token::CodeBlock(code) => {
+                // on lines of its own, styled line by line so newlines stay bare
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                for line in code.lines() {
+                    if styled {
+                        out.push_str(&line.cyan().to_string());
+                    } else {
+                        out.push_str(line);
+                    }
+                    out.push('\n');
+                }
+            }
+            Token::Quote(inner) => {
+                let inner = render_tokens(inner, mode, names);
+                if styled {
+                    out.push_str(&format!("| {}", inner).blue().bold().italic().to_string());
+                } else {
+                    out.push_str("> ");
+                    out.push_str(&inner);
+                }
+            }
+            Token::Newline => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            Token::User { id, label } => {
+                let name = label.clone().unwrap_or_else(|| names.user(id));
+                let name = name.trim_start_matches('@');
+                if styled {
+                    out.push_str(&format!("@{}", name.italic().bold().yellow()));
+                } else {
+                    out.push_str(&format!("@{}", name));
+                }
+            }
+            Token::Channel { id, label } => {
+                let name = format!("#{}", label.clone().unwrap_or_else(|| names.channel(id)));
+                out.push_str(&if styled {
+                    name.green().to_string()
+                } else {
+                    name
+                });
+            }
+            Token::Group { id, label } => {
+                let name = format!(
+                    "@{}",
+                    label.as_deref().unwrap_or(id).trim_start_matches('@')
+                );
+                out.push_str(&if styled {
+                    name.bold().yellow().to_string()
+                } else {
+                    name
+                });
+            }
+            Token::Broadcast(name) => {
+                let name = format!("@{}", name);
+                out.push_str(&if styled {
+                    name.bold().yellow().to_string()
+                } else {
+                    name
+                });
+            }
+            Token::Link { url, label } => {
+                let shown = url.strip_prefix("mailto:").unwrap_or(url);
+                match label.as_deref().filter(|label| *label != shown) {
+                    Some(label) if styled => {
+                        out.push_str(&format!("{} ({})", label.underline(), url.dimmed()))
+                    }
+                    Some(label) => out.push_str(&format!("{} ({})", label, url)),
+                    None if styled => out.push_str(&shown.underline().to_string()),
+                    None => out.push_str(shown),
+                }
+            }
+        }
+    }
+    out.trim_end_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Directory;
+
+    impl Names for Directory {
+        fn user(&mut self, id: &str) -> String {
+            match id {
+                "U0MOCK01" => "alice".to_string(),
+                _ => id.to_string(),
+            }
+        }
+
+        fn channel(&mut self, id: &str) -> String {
+            match id {
+                "C0MOCK01" => "incidents".to_string(),
+                _ => id.to_string(),
+            }
+        }
+    }
+
+    fn text(text: &str) -> Token {
+        Token::Text(text.to_string())
+    }
+
+    #[test]
+    fn tokenizes_mrkdwn() {
+        let cases = [
+            ("plain text", vec![text("plain text")]),
+            ("*bold*", vec![Token::Bold(vec![text("bold")])]),
+            (
+                "a _slanted_ ~struck~ word",
+                vec![
+                    text("a "),
+                    Token::Italic(vec![text("slanted")]),
+                    text(" "),
+                    Token::Strike(vec![text("struck")]),
+                    text(" word"),
+                ],
+            ),
+            (
+                "*bold _and italic_*",
+                vec![Token::Bold(vec![
+                    text("bold "),
+                    Token::Italic(vec![text("and italic")]),
+                ])],
+            ),
+            ("snake_case_name", vec![text("snake_case_name")]),
+            ("2*3*4", vec![text("2*3*4")]),
+            ("* not bold *", vec![text("* not bold *")]),
+            ("*unclosed", vec![text("*unclosed")]),
+            (
+                "run `make *all*`",
+                vec![text("run "), Token::Code("make *all*".to_string())],
+            ),
+            (
+                "```\nfn main() {}\n```",
+                vec![Token::CodeBlock("fn main() {}".to_string())],
+            ),
+            (
+                "<@U0MOCK01> and <@U0MOCK02|bob>",
+                vec![
+                    Token::User {
+                        id: "U0MOCK01".to_string(),
+                        label: None,
+                    },
+                    text(" and "),
+                    Token::User {
+                        id: "U0MOCK02".to_string(),
+                        label: Some("bob".to_string()),
+                    },
+                ],
+            ),
+            (
+                "<#C0MOCK01|incidents>",
+                vec![Token::Channel {
+                    id: "C0MOCK01".to_string(),
+                    label: Some("incidents".to_string()),
+                }],
+            ),
+            (
+                "<!here> <!subteam^S0MOCK|@oncall>",
+                vec![
+                    Token::Broadcast("here".to_string()),
+                    text(" "),
+                    Token::Group {
+                        id: "S0MOCK".to_string(),
+                        label: Some("@oncall".to_string()),
+                    },
+                ],
+            ),
+            (
+                "<!date^1392734382^{date_short}|Feb 18, 2014>",
+                vec![text("Feb 18, 2014")],
+            ),
+            (
+                "<https://example.com/a?b=1&amp;c=2|the docs>",
+                vec![Token::Link {
+                    url: "https://example.com/a?b=1&c=2".to_string(),
+                    label: Some("the docs".to_string()),
+                }],
+            ),
+            ("a &lt; b &amp;&amp; c &gt; d", vec![text("a < b && c > d")]),
+            (
+                "&gt; quoted\nreply",
+                vec![
+                    Token::Quote(vec![text("quoted")]),
+                    Token::Newline,
+                    text("reply"),
+                ],
+            ),
+            (
+                "&gt;&gt;&gt; all\nof this",
+                vec![
+                    Token::Quote(vec![text("all")]),
+                    Token::Newline,
+                    Token::Quote(vec![text("of this")]),
+                ],
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(tokenize(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn renders_plain_text() {
+        let cases = [
+            ("*bold* _italic_ ~strike~", "bold italic strike"),
+            ("hi <@U0MOCK01>", "hi @alice"),
+            ("<@U0MOCK02|bob> left", "@bob left"),
+            ("see <#C0MOCK01>", "see #incidents"),
+            ("<!channel> <!subteam^S0MOCK|@oncall>", "@channel @oncall"),
+            (
+                "<https://example.com|the docs>",
+                "the docs (https://example.com)",
+            ),
+            ("<https://example.com>", "https://example.com"),
+            (
+                "<mailto:bob@example.com|bob@example.com>",
+                "bob@example.com",
+            ),
+            ("&gt; quoted\n\n\nreply", "> quoted\nreply"),
+            (
+                "before\n```\nlet x = 1;\n```\nafter",
+                "before\nlet x = 1;\nafter",
+            ),
+            ("a &lt;tag&gt; &amp; `x &amp;&amp; y`", "a <tag> & x && y"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                render(input, Mode::Plain, &mut Directory),
+                expected,
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn styles_for_the_terminal() {
+        colored::control::set_override(true);
+        let rendered = render("*hi* <@U0MOCK01>", Mode::Styled, &mut Directory);
+        assert_eq!(
+            rendered,
+            format!("{} @{}", "hi".bold(), "alice".italic().bold().yellow())
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use crate::error::{Error, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::{ErrorKind, Write};
+
+/// How commands print messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Colored text for reading in a terminal
+    #[default]
+    Text,
+    /// A JSON array of message records
+    Json,
+    /// One JSON message record per line
+    Ndjson,
+    /// Message records as a table with a header row
+    Csv,
+}
+
+/// A message with its user and channel resolved, as the machine-readable
+/// formats print it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Record {
+    pub ts: String,
+    /// `ts` as an ISO 8601 time in UTC.
+    pub time: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub channel_id: String,
+    pub channel: String,
+    pub text: String,
+    pub thread_ts: Option<String>,
+    pub permalink: String,
+}
+
+/// Writes `records` to stdout in one go, so a JSON array is never split.
+/// A reader that stops early, like `head`, is a normal end of output.
+pub fn write_records(format: Format, records: &[Record]) -> Result<()> {
+    match write(format, records, &mut std::io::stdout().lock()) {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| Error::Output(e.to_string())),
+    }
+}
+
+fn write(format: Format, records: &[Record], out: &mut impl Write) -> std::io::Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, records)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut *out);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        // commands print text themselves, styled, and only hand records over
+        // for the other formats
+        Format::Text => unreachable!("records are not written as text"),
+    }
+    out.flush()
+}
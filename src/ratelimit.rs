@@ -0,0 +1,145 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Retries after a 429 or a transient failure before giving up.
+pub const MAX_RETRIES: u32 = 5;
+
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_CAP: Duration = Duration::from_secs(30);
+
+/// Slack's documented rate limit tiers, in requests per minute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tier {
+    Two,
+    Three,
+    Four,
+}
+
+impl Tier {
+    pub fn for_method(method: &str) -> Tier {
+        match method {
+            "users.list" | "conversations.list" | "search.messages" => Tier::Two,
+            "conversations.history" | "conversations.replies" | "conversations.members" => {
+                Tier::Three
+            }
+            "auth.test" | "chat.postMessage" => Tier::Four,
+            _ => Tier::Three,
+        }
+    }
+
+    pub fn per_minute(&self) -> u32 {
+        match self {
+            Tier::Two => 20,
+            Tier::Three => 50,
+            Tier::Four => 100,
+        }
+    }
+}
+
+/// Token bucket per API method. A bucket holds a minute's worth of requests
+/// for the method's tier so short bursts go straight through, and refills
+/// continuously after that.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Blocks until `method` has budget left, then spends one request of it.
+    pub fn acquire(&self, method: &str) {
+        let per_minute = Tier::for_method(method).per_minute() as f64;
+        let refill_per_sec = per_minute / 60.0;
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                let bucket = buckets.entry(method.to_string()).or_insert(Bucket {
+                    tokens: per_minute,
+                    updated: now,
+                });
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(per_minute);
+                bucket.updated = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec)
+            };
+            log::debug!("{} over its budget, waiting {:?}", method, wait);
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Makes every caller of `method` wait `delay`, used when Slack asks us to
+    /// back off with `Retry-After`.
+    pub fn pause(&self, method: &str, delay: Duration) {
+        let refill_per_sec = Tier::for_method(method).per_minute() as f64 / 60.0;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        // going into debt delays the next acquire by exactly `delay`
+        buckets.insert(
+            method.to_string(),
+            Bucket {
+                tokens: 1.0 - delay.as_secs_f64() * refill_per_sec,
+                updated: Instant::now(),
+            },
+        );
+    }
+}
+
+/// Exponential backoff with full jitter for the given attempt, starting at 0.
+pub fn backoff(attempt: u32) -> Duration {
+    let ceiling = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_CAP);
+    let millis = rand::thread_rng().gen_range(0..=ceiling.as_millis() as u64);
+    Duration::from_millis(millis).max(BACKOFF_BASE / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_stays_within_bounds() {
+        for attempt in [0, 1, 3, 10, 40, u32::MAX] {
+            let ceiling = BACKOFF_BASE
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(BACKOFF_CAP);
+            for _ in 0..50 {
+                let delay = backoff(attempt);
+                assert!(delay >= BACKOFF_BASE / 2, "{} {:?}", attempt, delay);
+                assert!(delay <= ceiling, "{} {:?}", attempt, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn methods_map_to_their_tier() {
+        let cases = [
+            ("users.list", Tier::Two),
+            ("conversations.list", Tier::Two),
+            ("search.messages", Tier::Two),
+            ("conversations.history", Tier::Three),
+            ("conversations.replies", Tier::Three),
+            ("conversations.members", Tier::Three),
+            ("auth.test", Tier::Four),
+            ("chat.postMessage", Tier::Four),
+            ("emoji.list", Tier::Three),
+        ];
+        for (method, tier) in cases {
+            assert_eq!(Tier::for_method(method), tier, "{}", method);
+        }
+    }
+}
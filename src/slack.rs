@@ -1,22 +1,31 @@
 use crate::cache::cache::Cache;
 use crate::decryptor::UnixCookieDecryptor;
+use crate::error::{Error, Result};
 use crate::model::domain::User;
 use colored::Colorize;
 use serde_json::Value;
-use core::f64;
 use fake::Fake;
 use itertools::Itertools;
 use keyring::Entry;
 use rand::Rng;
 use regex::Regex;
-use reqwest::{blocking::Client, header::HeaderMap};
+use crate::ratelimit::{self, RateLimiter};
+use crate::transport::{Body, Method, Request, Response, Transport};
+use reqwest::header::{HeaderMap, HeaderValue};
 use serde::de::DeserializeOwned;
-use std::collections::{hash_map, HashMap, HashSet};
-use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
 use std::path::Path;
-use crate::model::message::Message;
+use std::sync::{mpsc, Arc, LazyLock};
 use threadpool::ThreadPool;
-use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::model::envelope::Envelope;
+use crate::model::message::Message;
+use crate::export::{self, Transcript};
+use crate::content;
+use crate::mrkdwn;
+use crate::output::{self, Format, Record};
+use crate::time;
 
 macro_rules! collection {
     // map-like
@@ -31,18 +40,107 @@ macro_rules! collection {
     }};
 }
 
-// #[derive(Clone)]
+/// PBKDF2 iterations Chromium uses when deriving the cookie key.
+#[cfg(target_os = "macos")]
+const COOKIE_PBKDF2_ROUNDS: u32 = 1003;
+#[cfg(not(target_os = "macos"))]
+const COOKIE_PBKDF2_ROUNDS: u32 = 1;
+
+/// Chromium's password on Linux when no keyring is available.
+const LINUX_FALLBACK_PASSWORD: &[u8] = b"peanuts";
+
 pub struct Slack {
-    auth: Option<Auth>,
-    client: Client,
-    team: Option<String>,
+    client: Arc<Client>,
     cache: Box<dyn Cache>,
     user_map: HashMap<String, String>,
+    format: Format,
+    clock: time::Clock,
+}
+
+/// What it takes to call the API: credentials, transport and rate limits.
+/// Kept apart from the cache so worker threads can share it.
+pub struct Client {
+    /// `None` only for offline clients, whose requests carry no credentials.
+    auth: Option<Auth>,
+    transport: Box<dyn Transport>,
+    /// Replaces `https://{team}.slack.com` for every request, e.g. a local mock.
+    base_url: Option<url::Url>,
+    team: String,
+    limiter: RateLimiter,
+}
+
+/// Environment variable holding a user (`xoxp-`) or bot (`xoxb-`) token.
+pub const TOKEN_ENV_VAR: &str = "SLACK_TOKEN";
+
+/// Environment variable pointing the client at another Slack API host.
+pub const API_URL_ENV_VAR: &str = "SLACK_API_URL";
+
+/// Resolves the API base url from the `--api-url` flag, then `SLACK_API_URL`.
+/// `None` means the real `https://{team}.slack.com`.
+pub fn base_url(flag: Option<&str>) -> Result<Option<url::Url>> {
+    let base_url = match flag {
+        Some(url) => url.to_string(),
+        None => match std::env::var(API_URL_ENV_VAR).ok().filter(|x| !x.is_empty()) {
+            Some(url) => url,
+            None => return Ok(None),
+        },
+    };
+    // without a trailing slash `join` would drop the last path segment
+    let base_url = format!("{}/", base_url.trim_end_matches('/'));
+    let url = url::Url::parse(&base_url)
+        .map_err(|e| Error::Input(format!("invalid api url {}: {}", base_url, e)))?;
+    Ok(Some(url))
+}
+
+/// Keyring service under which tokens are looked up, keyed by team name.
+const TOKEN_KEYRING_SERVICE: &str = "slack-rs";
+
+/// Keyring service caching the token scraped during cookie auth, keyed by team name.
+const SESSION_KEYRING_SERVICE: &str = "slack-rs-session";
+
+/// Replies fetched per `conversations.replies` request; Slack allows up to 1000.
+const REPLIES_PAGE_SIZE: usize = 200;
+
+/// A Slack Web API call, run with `Client::execute`. Calls without a body
+/// are sent as GET with the params in the query string, the rest as POST.
+pub struct ApiRequest<'a> {
+    path: &'a str,
+    params: HashMap<&'a str, &'a str>,
+    body: Body,
+    use_team_name: bool,
+}
+
+impl<'a> ApiRequest<'a> {
+    pub fn new(path: &'a str) -> Self {
+        Self {
+            path,
+            params: HashMap::new(),
+            body: Body::Empty,
+            use_team_name: true,
+        }
+    }
+
+    pub fn params(mut self, params: HashMap<&'a str, &'a str>) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn json<B: serde::Serialize>(mut self, body: &B) -> Result<Self> {
+        self.body = Body::Json(serde_json::to_string(body)?);
+        Ok(self)
+    }
+
+    /// Whether to call `{team}.slack.com` rather than `slack.com`.
+    pub fn use_team_name(mut self, use_team_name: bool) -> Self {
+        self.use_team_name = use_team_name;
+        self
+    }
 }
 
 #[derive(Clone)]
 pub enum Auth {
     Cookie(CookieAuth),
+    Token(TokenAuth),
 }
 
 #[derive(Clone)]
@@ -51,129 +149,206 @@ pub struct CookieAuth {
     token: Option<String>,
 }
 
-pub fn new(team: &str, cache: Box<dyn Cache>) -> Result<Slack, Box<dyn Error>> {
-    let mut s = Slack {
-        auth: None,
-        client: Client::new(),
-        team: Some(team.into()),
-        cache: cache,
-        user_map: HashMap::new(),
-    };
-    s.auth(team)?;
-    Ok(s)
+#[derive(Clone)]
+pub struct TokenAuth {
+    token: String,
 }
 
-impl Slack {
-    fn password() -> Result<Vec<u8>, Box<dyn Error>> {
-        let account_names = vec!["Slack Key", "Slack", "Slack App Store Key"];
+/// Bounds for `conversations.history`, as Slack timestamps.
+#[derive(Debug, Clone, Default)]
+pub struct Range {
+    pub oldest: Option<String>,
+    pub latest: Option<String>,
+}
 
-        for account_name in account_names {
-            match Slack::cookie_password_from_keychain(account_name) {
-                Ok(password) => return Ok(password),
-                _ => continue,
-            }
+/// Connection and output settings for `new` beyond the team and cache.
+#[derive(Default)]
+pub struct Options<'a> {
+    pub token_file: Option<&'a Path>,
+    /// Replaces `https://{team}.slack.com` for every request, e.g. a local mock.
+    pub base_url: Option<url::Url>,
+    /// Skip authentication entirely, for transports that never reach Slack
+    /// such as a replay of recorded traffic.
+    pub offline: bool,
+    pub format: Format,
+    pub time_format: time::TimeFormat,
+    /// `local`, an IANA timezone, or `@name` of a cached user.
+    pub tz: &'a str,
+    pub date_headers: bool,
+}
+
+pub fn new(
+    team: &str,
+    cache: Box<dyn Cache>,
+    transport: Box<dyn Transport>,
+    options: Options,
+) -> Result<Slack> {
+    let clock = time::Clock {
+        format: options.time_format,
+        zone: zone(cache.as_ref(), team, options.tz)?,
+        date_headers: options.date_headers,
+    };
+    let mut client = Client {
+        auth: None,
+        transport,
+        base_url: options.base_url,
+        team: team.into(),
+        limiter: RateLimiter::new(),
+    };
+    if !options.offline {
+        match Slack::token(team, options.token_file)? {
+            Some(token) => client.auth = Some(Auth::Token(TokenAuth { token })),
+            None => client.auth(team)?,
         }
-        Err("No password found for any account name".into())
     }
+    Ok(Slack {
+        client: Arc::new(client),
+        cache,
+        user_map: HashMap::new(),
+        format: options.format,
+        clock,
+    })
+}
 
-    fn slack_config_dir() -> Option<String> {
-        let home = std::env::var("HOME").ok()?;
-        let first = format!("{home}/Library/Application Support/Slack");
-        let second=format!("{home}/Library/Containers/com.tinyspeck.slackmacgap/Data/Library/Application Support/Slack");
-        if Path::new(&first).exists() {
-            Some(first)
-        } else {
-            Some(second)
-        }
+/// Resolves `--tz`, looking `@name` up in the users cached by `sync`.
+fn zone(cache: &dyn Cache, team: &str, tz: &str) -> Result<time::Zone> {
+    if tz.is_empty() {
+        return Ok(time::Zone::Local);
+    }
+    if let Some(zone) = time::Zone::parse(tz) {
+        return Ok(zone);
     }
+    let name = tz.strip_prefix('@').ok_or_else(|| {
+        Error::Input(format!(
+            "unknown timezone {}, expected local, a name like Europe/London or @user",
+            tz
+        ))
+    })?;
+    let user = cache
+        .get_user_id(team, name)
+        .and_then(|id| cache.get_user(team, &id))
+        .ok_or_else(|| {
+            Error::Input(format!(
+                "no user @{} in {}, run `sync --team {}` first",
+                name, team, team
+            ))
+        })?;
+    time::Zone::parse(&user.tz).ok_or_else(|| {
+        Error::Input(format!(
+            "@{} has no timezone in the cache, run `sync --team {}` to fetch it",
+            name, team
+        ))
+    })
+}
 
-    fn cookie_password_from_keychain(account_name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-        let service = "Slack Safe Storage";
-        let keyring = Entry::new(service, account_name)?;
-        let password = keyring.get_password()?;
-        Ok(password.into_bytes())
+/// Redraws a `fetched n users` line while `sync` pages through a terminal.
+/// Redirected stderr gets only the total from `progress_done`.
+fn progress(what: &str, count: usize) {
+    if std::io::stderr().is_terminal() {
+        eprint!("\rfetched {} {}", count, what);
+    } else {
+        log::debug!("fetched {} {}", count, what);
     }
+}
 
-    fn format_cookie(key: &str, value: &str) -> String {
-        format!("{}={}", key, value)
+fn progress_done(what: &str, count: usize) {
+    if std::io::stderr().is_terminal() {
+        eprintln!();
+    } else {
+        eprintln!("fetched {} {}", count, what);
     }
+}
 
-    fn get_cookie_value_encrypted_value() -> Result<(String, Vec<u8>), Box<dyn Error>> {
-        let cookie_dbpath: String;
-        match Slack::slack_config_dir() {
-            Some(path) => {
-                cookie_dbpath = format!("{}/Cookies", path);
-            }
-            None => {
-                return Err("Could not find slack config dir".into());
-            }
-        }
+impl Client {
+    // cookie auth scrapes the api token from the team's homepage, reusing the
+    // last scraped token while auth.test still accepts it
+    fn auth(&mut self, team: &str) -> Result<()> {
+        let (value, encrypted_value) = Slack::get_cookie_value_encrypted_value()?;
 
-        let connection = sqlite::open(cookie_dbpath)?;
-        let query = r#"SELECT value, encrypted_value FROM cookies where host_key=".slack.com" AND name="d""#;
-        let mut statement = connection.prepare(query)?;
+        let cookie = if value.is_empty() {
+            Slack::decrypt_cookie(encrypted_value)?
+        } else {
+            value
+        };
 
-        let mut value: Option<String> = None;
-        let mut encrypted_value: Option<Vec<u8>> = None;
+        log::info!("Cookie: {}", cookie);
 
-        if let sqlite::State::Row = statement.next()? {
-            // Assign values if a row is found
-            value = Some(statement.read::<String, _>(0)?);
-            encrypted_value = Some(statement.read::<Vec<u8>, _>(1)?);
+        let session = Entry::new(SESSION_KEYRING_SERVICE, team).ok();
+        if let Some(token) = session.as_ref().and_then(|s| s.get_password().ok()) {
+            self.auth = Some(Auth::Cookie(CookieAuth {
+                cookie: cookie.clone(),
+                token: Some(token),
+            }));
+            match self.auth_test()? {
+                None => {
+                    log::info!("reusing cached token for {}", team);
+                    return Ok(());
+                }
+                Some(error) => log::info!("cached token rejected ({}), scraping again", error),
+            }
         }
-        let mut encrypted_value = encrypted_value.unwrap();
-        encrypted_value = encrypted_value[3..].to_vec(); // to remove the version bytes.
-        Ok((value.unwrap(), encrypted_value))
-    }
 
-    fn get_channel(&self, channel_name: &str, channel_id: &str) -> String {
-        if !channel_name.is_empty() {
-            return channel_name.to_string();
+        let token = self.scrape_token(team, &cookie)?;
+        if let Some(Err(e)) = session.map(|s| s.set_password(&token)) {
+            log::warn!("could not cache token in keyring: {}", e);
         }
-        let channel = self
-            .cache
-            .get_channel(self.team.as_ref().unwrap(), channel_id)
-            .unwrap_or_else(|| crate::model::domain::Channel {
-                id: channel_id.to_string(),
-                name: channel_id.to_string(),
-                is_channel: true,
-            });
-        channel.name
-    }
-
-    //auth currently only supports cookie auth
-    fn auth(&mut self, team: &str) -> Result<(), Box<dyn Error>> {
-        let (_value, encrypted_value) = Slack::get_cookie_value_encrypted_value()?;
 
-        let password = Slack::password()?;
-
-        let cookie =
-            String::from_utf8(UnixCookieDecryptor::new(1003).decrypt(encrypted_value, &password)?)
-                .unwrap();
-
-        log::info!("Cookie: {}", cookie);
+        self.auth = Some(Auth::Cookie(CookieAuth {
+            cookie,
+            token: Some(token),
+        }));
+        Ok(())
+    }
 
+    fn scrape_token(&self, team: &str, cookie: &str) -> Result<String> {
         // add cookie
         let mut headers = HeaderMap::new();
-        headers.insert("Cookie", Slack::format_cookie("d", &cookie).parse()?);
+        headers.insert("Cookie", Slack::header_value(&Slack::format_cookie("d", cookie))?);
 
-        let res = self
-            .client
-            .get(format!("https://{team}.slack.com").as_str())
-            .headers(headers)
-            .send()?;
+        let url = match &self.base_url {
+            Some(base_url) => base_url.to_string(),
+            None => format!("https://{team}.slack.com"),
+        };
+        let request = Request {
+            method: Method::Get,
+            url: url
+                .parse()
+                .map_err(|e| Error::Input(format!("invalid team url {}: {}", url, e)))?,
+            headers,
+            body: Body::Empty,
+        };
+        let res = self.transport.send(&request)?;
 
-        let response = res.text()?;
+        let response = String::from_utf8_lossy(&res.body);
         let re = Regex::new(r#""api_token":"([^"]+)""#).unwrap();
-        let token = re.captures(&response).unwrap().get(1).unwrap().as_str();
+        let token = re
+            .captures(&response)
+            .and_then(|c| c.get(1))
+            .ok_or_else(|| {
+                Error::Auth("could not find api_token, is the Slack cookie still valid?".into())
+            })?
+            .as_str();
 
         log::info!("Token: {}", token);
+        Ok(token.to_string())
+    }
 
-        self.auth = Some(Auth::Cookie(CookieAuth {
-            cookie,
-            token: Some(token.into()),
-        }));
-        Ok(())
+    /// Checks the current credentials with `auth.test`. Returns the Slack error when
+    /// the token is no longer accepted and `None` when it is still good.
+    fn auth_test(&self) -> Result<Option<String>> {
+        use crate::model::auth as model;
+        match self.api::<model::Root>("auth.test", collection! {}, true) {
+            Ok(_) => Ok(None),
+            Err(Error::Api { error, .. })
+                if matches!(
+                    error.as_str(),
+                    "invalid_auth" | "token_revoked" | "token_expired" | "not_authed"
+                ) =>
+            {
+                Ok(Some(error))
+            }
+            Err(e) => Err(e),
+        }
     }
 
     pub fn post_api<T, B>(
@@ -182,76 +357,17 @@ impl Slack {
         params: HashMap<&str, &str>,
         body: B,
         use_team_name: bool,
-    ) -> Result<T, Box<dyn Error>>
+    ) -> Result<T>
     where
         T: DeserializeOwned,
         B: serde::Serialize,
     {
-        // URL construction (same as api<T>)
-        let url = if use_team_name {
-            format!(
-                "https://{}.slack.com/api/{}",
-                self.team.as_ref().unwrap(),
-                path
-            )
-        } else {
-            format!("https://slack.com/api/{}", path)
-        };
-        let mut url = url.parse::<url::Url>().unwrap();
-        
-        // Add query parameters (same as api<T>)
-        params.iter().for_each(|(k, v)| {
-            url.query_pairs_mut().append_pair(k, v);
-        });
-        
-        // Headers setup (same as api<T>)
-        let mut headers = HeaderMap::new();
-        let auth = self.auth.as_ref().unwrap();
-        match auth {
-            Auth::Cookie(cookie_auth) => {
-                headers.insert(
-                    "Cookie",
-                    Slack::format_cookie("d", &cookie_auth.cookie)
-                        .parse()
-                        .unwrap(),
-                );
-                headers.insert(
-                    "Authorization",
-                    format!("Bearer {}", cookie_auth.token.as_ref().unwrap())
-                        .parse()
-                        .unwrap(),
-                );
-                headers.insert(
-                    "Content-Type",
-                    "application/json; charset=utf-8".parse().unwrap(),
-                );
-            }
-        }
-        
-        // Log URL (same as api<T>)
-        log::info!("url: {}", url);
-        
-        // Serialize body to JSON
-        let json_body = serde_json::to_string(&body)?;
-        
-        // Send POST request with body (different from api<T>)
-        let res = self
-            .client
-            .post(url.as_ref())
-            .headers(headers)
-            .body(json_body)
-            .send()
-            .unwrap();
-        
-        // Process response (same as api<T>)
-        let body_bytes = res.bytes()?;
-        let response_size = body_bytes.len();
-        log::info!("Response size: {}", response_size);
-        log::debug!("Response: {:?}", body_bytes);
-    
-        let parsed_response: T = serde_json::from_slice::<T>(&body_bytes)?;
-    
-        Ok(parsed_response)
+        self.execute(
+            ApiRequest::new(path)
+                .params(params)
+                .json(&body)?
+                .use_team_name(use_team_name),
+        )
     }
 
     pub fn api<T>(
@@ -259,167 +375,594 @@ impl Slack {
         path: &str,
         params: HashMap<&str, &str>,
         use_team_name: bool,
-    ) -> Result<T, Box<dyn Error>>
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute(
+            ApiRequest::new(path)
+                .params(params)
+                .use_team_name(use_team_name),
+        )
+    }
+
+    /// Runs a Web API call through the transport and decodes its response.
+    pub fn execute<T>(&self, request: ApiRequest) -> Result<T>
     where
         T: DeserializeOwned,
     {
-        let url = if use_team_name {
-            format!(
-                "https://{}.slack.com/api/{}",
-                self.team.as_ref().unwrap(),
-                path
-            )
+        let path = request.path;
+        let url = self.api_url(path, &request.params, request.use_team_name)?;
+        log::info!("url: {}", url);
+        let request = Request {
+            method: match request.body {
+                Body::Empty => Method::Get,
+                _ => Method::Post,
+            },
+            url,
+            headers: self.headers()?,
+            body: request.body,
+        };
+        let res = self.send_with_retry(path, &request)?;
+        Client::parse_response(path, res)
+    }
+
+    fn api_url(
+        &self,
+        path: &str,
+        params: &HashMap<&str, &str>,
+        use_team_name: bool,
+    ) -> Result<url::Url> {
+        let url = if let Some(base_url) = &self.base_url {
+            base_url.join(&format!("api/{}", path)).map_err(|e| {
+                Error::Input(format!("invalid api url {}api/{}: {}", base_url, path, e))
+            })?
         } else {
-            format!("https://slack.com/api/{}", path)
+            let url = if use_team_name {
+                format!("https://{}.slack.com/api/{}", &self.team, path)
+            } else {
+                format!("https://slack.com/api/{}", path)
+            };
+            url.parse::<url::Url>()
+                .map_err(|e| Error::Input(format!("invalid api url {}: {}", url, e)))?
         };
-        let mut url = url.parse::<url::Url>().unwrap();
+        let mut url = url;
         params.iter().for_each(|(k, v)| {
             url.query_pairs_mut().append_pair(k, v);
         });
+        Ok(url)
+    }
+
+    fn headers(&self) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
-        let auth = self.auth.as_ref().unwrap();
+        let auth = match self.auth.as_ref() {
+            Some(auth) => auth,
+            None => return Ok(headers),
+        };
         match auth {
             Auth::Cookie(cookie_auth) => {
+                let token = cookie_auth
+                    .token
+                    .as_ref()
+                    .ok_or_else(|| Error::Auth("no api token for cookie session".into()))?;
                 headers.insert(
                     "Cookie",
-                    Slack::format_cookie("d", &cookie_auth.cookie)
-                        .parse()
-                        .unwrap(),
+                    Slack::header_value(&Slack::format_cookie("d", &cookie_auth.cookie))?,
                 );
                 headers.insert(
                     "Authorization",
-                    format!("Bearer {}", cookie_auth.token.as_ref().unwrap())
-                        .parse()
-                        .unwrap(),
+                    Slack::header_value(&format!("Bearer {}", token))?,
                 );
+            }
+            Auth::Token(token_auth) => {
                 headers.insert(
-                    "Content-Type",
-                    "application/json; charset=utf-8".parse().unwrap(),
+                    "Authorization",
+                    Slack::header_value(&format!("Bearer {}", token_auth.token))?,
                 );
             }
         }
-        log::info!("url: {}", url);
-        let res = self
-            .client
-            .get(url.as_ref())
-            .headers(headers)
-            .send()
-            .unwrap();
+        Ok(headers)
+    }
+
+    /// Sends `request` within the method's rate limit budget. A 429 waits out
+    /// `Retry-After` and transient failures (5xx, connection errors) back off
+    /// exponentially with jitter, up to `MAX_RETRIES` times. A POST that timed
+    /// out or got a 5xx may still have gone through, so only connection
+    /// errors and 429s retry it.
+    fn send_with_retry(&self, path: &str, request: &Request) -> Result<Response> {
+        let idempotent = request.method == Method::Get;
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire(path);
+            let res = match self.transport.send(request) {
+                Ok(res) => res,
+                Err(Error::Transport(e))
+                    if attempt < ratelimit::MAX_RETRIES
+                        && (e.is_connect() || (idempotent && e.is_timeout())) =>
+                {
+                    let delay = ratelimit::backoff(attempt);
+                    log::warn!("{} failed ({}), retrying in {:?}", path, e, delay);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if res.status == 429 {
+                if attempt >= ratelimit::MAX_RETRIES {
+                    return Err(Error::RateLimited {
+                        method: path.to_string(),
+                        retry_after: res.retry_after,
+                    });
+                }
+                let delay = res
+                    .retry_after
+                    .map(Duration::from_secs)
+                    .unwrap_or_else(|| ratelimit::backoff(attempt));
+                log::warn!("{} rate limited, retrying in {:?}", path, delay);
+                self.limiter.pause(path, delay);
+                attempt += 1;
+                continue;
+            }
+            if idempotent && (500..600).contains(&res.status) && attempt < ratelimit::MAX_RETRIES {
+                let delay = ratelimit::backoff(attempt);
+                log::warn!("{} returned {}, retrying in {:?}", path, res.status, delay);
+                std::thread::sleep(delay);
+                attempt += 1;
+                continue;
+            }
+            return Ok(res);
+        }
+    }
+
+    fn parse_response<T>(path: &str, res: Response) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        if !(200..300).contains(&res.status) {
+            return Err(Error::Api {
+                method: path.to_string(),
+                error: format!("http_{}", res.status),
+                messages: Vec::new(),
+            });
+        }
         // calculate the size of response
-        let body_bytes = res.bytes()?;
+        let body_bytes = res.body;
         let response_size = body_bytes.len();
         log::info!("Response size: {}", response_size);
         log::debug!("Response: {:?}", body_bytes);
 
-        let parsed_response: T = serde_json::from_slice::<T>(&body_bytes)?;
+        let envelope = serde_json::from_slice::<Envelope>(&body_bytes)
+            .map_err(|e| Error::Parse(format!("{}: {}", path, e)))?;
+        if let Some(warning) = &envelope.warning {
+            log::warn!("{}: {}", path, warning);
+        }
+        for warning in &envelope.response_metadata.warnings {
+            log::warn!("{}: {}", path, warning);
+        }
+        if !envelope.ok {
+            return Err(Error::Api {
+                method: path.to_string(),
+                error: envelope.error.unwrap_or_else(|| "unknown_error".into()),
+                messages: envelope.response_metadata.messages,
+            });
+        }
+        for message in &envelope.response_metadata.messages {
+            log::info!("{}: {}", path, message);
+        }
+
+        serde_json::from_slice::<T>(&body_bytes)
+            .map_err(|e| Error::Parse(format!("{}: {}", path, e)))
+    }
+
+    /// Follows `conversations.replies` through every page, or until `limit`
+    /// messages. `has_more` on the result tells whether the thread goes on.
+    fn thread_messages(
+        &self,
+        channel: &str,
+        ts: &str,
+        limit: Option<usize>,
+    ) -> Result<crate::model::replies::Root> {
+        use crate::model::replies as model;
+        let mut thread = model::Root {
+            ok: true,
+            ..model::Root::default()
+        };
+        let mut cursor = String::new();
+        loop {
+            let page_size = limit
+                .map_or(REPLIES_PAGE_SIZE, |limit| {
+                    (limit - thread.messages.len()).min(REPLIES_PAGE_SIZE)
+                })
+                .to_string();
+            let mut params: HashMap<&str, &str> = collection! {"channel"=> channel, "ts"=>ts, "limit"=>page_size.as_str(), "inclusive"=>"true"};
+            if !cursor.is_empty() {
+                params.insert("cursor", &cursor);
+            }
+            let res = self.api::<model::Root>("conversations.replies", params, true)?;
+            thread.messages.extend(res.messages);
+            cursor = res.response_metadata.next_cursor;
+            thread.has_more = res.has_more && !cursor.is_empty();
+            if let Some(limit) = limit.filter(|limit| thread.messages.len() >= *limit) {
+                thread.has_more |= thread.messages.len() > limit;
+                thread.messages.truncate(limit);
+                break;
+            }
+            if !thread.has_more {
+                break;
+            }
+        }
+        Ok(thread)
+    }
+}
+
+impl Slack {
+    fn password() -> Result<Vec<u8>> {
+        let account_names = vec!["Slack Key", "Slack", "Slack App Store Key"];
+
+        for account_name in account_names {
+            match Slack::cookie_password_from_keychain(account_name) {
+                Ok(password) => return Ok(password),
+                _ => continue,
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if let Ok(password) = Slack::cookie_password_from_secret_service() {
+            return Ok(password);
+        }
+        Err(Error::Auth(
+            "no cookie password found in the keychain for any account name".into(),
+        ))
+    }
+
+    /// Picks the password a cookie was encrypted with based on its version prefix.
+    /// On Linux `v10` cookies always use the hard-coded Chromium password, and `v11`
+    /// ones fall back to it when no Secret Service entry exists.
+    fn cookie_password(version: &[u8]) -> Result<Vec<u8>> {
+        Slack::choose_cookie_password(version, cfg!(target_os = "linux"), Slack::password)
+    }
+
+    /// `cookie_password` with the platform and the stored password lookup
+    /// passed in.
+    fn choose_cookie_password(
+        version: &[u8],
+        linux: bool,
+        stored: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        if linux {
+            if version == b"v10" {
+                return Ok(LINUX_FALLBACK_PASSWORD.to_vec());
+            }
+            return Ok(stored().unwrap_or_else(|e| {
+                log::info!("falling back to default cookie password: {}", e);
+                LINUX_FALLBACK_PASSWORD.to_vec()
+            }));
+        }
+        stored()
+    }
+
+    fn slack_config_dirs() -> Vec<String> {
+        let home = match std::env::var("HOME") {
+            Ok(home) => home,
+            Err(_) => return Vec::new(),
+        };
+        let config_home = std::env::var("XDG_CONFIG_HOME")
+            .ok()
+            .filter(|x| !x.is_empty());
+        Slack::config_dirs(&home, config_home, cfg!(target_os = "macos"))
+    }
+
+    /// Where the desktop client keeps its data, in the order they are searched:
+    /// the native app, then the Flatpak and Snap sandboxes on Linux, or the
+    /// direct download then the App Store build on macOS.
+    fn config_dirs(home: &str, config_home: Option<String>, macos: bool) -> Vec<String> {
+        if macos {
+            return vec![
+                format!("{home}/Library/Application Support/Slack"),
+                format!("{home}/Library/Containers/com.tinyspeck.slackmacgap/Data/Library/Application Support/Slack"),
+            ];
+        }
+        let config_home = config_home.unwrap_or_else(|| format!("{home}/.config"));
+        vec![
+            format!("{config_home}/Slack"),
+            format!("{home}/.var/app/com.slack.Slack/config/Slack"),
+            format!("{home}/snap/slack/current/.config/Slack"),
+        ]
+    }
+
+    /// The first cookie store in `dirs` that `exists`. Newer Chromium builds
+    /// moved it under Network/.
+    fn cookie_db_path(dirs: &[String], exists: impl Fn(&str) -> bool) -> Result<String> {
+        if dirs.is_empty() {
+            return Err(Error::Auth(
+                "could not look for the Slack desktop app's Cookies, HOME is not set".into(),
+            ));
+        }
+        dirs.iter()
+            .flat_map(|dir| {
+                [
+                    format!("{}/Cookies", dir),
+                    format!("{}/Network/Cookies", dir),
+                ]
+            })
+            .find(|path| exists(path))
+            .ok_or_else(|| {
+                Error::Auth(format!(
+                    "could not find the Slack desktop app's Cookies, searched {}",
+                    dirs.join(", ")
+                ))
+            })
+    }
+
+    fn cookie_password_from_keychain(account_name: &str) -> Result<Vec<u8>> {
+        let service = "Slack Safe Storage";
+        let keyring = Entry::new(service, account_name)?;
+        let password = keyring.get_password()?;
+        Ok(password.into_bytes())
+    }
+
+    /// Chromium stores its key under the `application` attribute rather than the
+    /// service/username pair `keyring` searches by, so query Secret Service directly.
+    #[cfg(target_os = "linux")]
+    fn cookie_password_from_secret_service() -> Result<Vec<u8>> {
+        use dbus_secret_service::{EncryptionType, SecretService};
+        let secret_service_error = |e: dbus_secret_service::Error| Error::Auth(e.to_string());
+        let ss = SecretService::connect(EncryptionType::Plain).map_err(secret_service_error)?;
+        let search = ss
+            .search_items(collection! {"application" => "Slack"})
+            .map_err(secret_service_error)?;
+        let item = search
+            .unlocked
+            .first()
+            .or(search.locked.first())
+            .ok_or_else(|| Error::Auth("no Slack entry in Secret Service".into()))?;
+        item.ensure_unlocked().map_err(secret_service_error)?;
+        item.get_secret().map_err(secret_service_error)
+    }
+
+    fn format_cookie(key: &str, value: &str) -> String {
+        format!("{}={}", key, value)
+    }
+
+    fn header_value(value: &str) -> Result<HeaderValue> {
+        value
+            .parse()
+            .map_err(|_| Error::Auth("credentials contain characters not allowed in a header".into()))
+    }
+
+    fn get_cookie_value_encrypted_value() -> Result<(String, Vec<u8>)> {
+        let cookie_dbpath =
+            Slack::cookie_db_path(&Slack::slack_config_dirs(), |path| Path::new(path).exists())?;
+        log::info!("cookie db: {}", cookie_dbpath);
+
+        // the cookie store isn't our cache, so report its failures as auth errors
+        let cookie_db_error = |e: sqlite::Error| Error::Auth(format!("cookie db: {}", e));
+        let connection = sqlite::open(cookie_dbpath).map_err(cookie_db_error)?;
+        let query = r#"SELECT value, encrypted_value FROM cookies where host_key=".slack.com" AND name="d""#;
+        let mut statement = connection.prepare(query).map_err(cookie_db_error)?;
+
+        if let sqlite::State::Row = statement.next().map_err(cookie_db_error)? {
+            let value = statement.read::<String, _>(0).map_err(cookie_db_error)?;
+            let encrypted_value = statement.read::<Vec<u8>, _>(1).map_err(cookie_db_error)?;
+            return Ok((value, encrypted_value));
+        }
+        Err(Error::Auth(
+            "no slack cookie found, log in to the Slack desktop app first".into(),
+        ))
+    }
+
+    /// Decrypts the `d` cookie, which is prefixed by its Chromium version (`v10`/`v11`).
+    fn decrypt_cookie(encrypted_value: Vec<u8>) -> Result<String> {
+        if encrypted_value.len() < 3 {
+            return Err(Error::Auth("encrypted cookie is too short".into()));
+        }
+        let (version, encrypted_value) = encrypted_value.split_at(3);
+        if version != b"v10" && version != b"v11" {
+            return Err(Error::Auth(format!(
+                "unsupported cookie version {}",
+                String::from_utf8_lossy(version)
+            )));
+        }
+        let password = Slack::cookie_password(version)?;
+        let cookie = UnixCookieDecryptor::new(COOKIE_PBKDF2_ROUNDS)
+            .decrypt(encrypted_value.to_vec(), &password)?;
+        String::from_utf8(cookie).map_err(|e| Error::Auth(format!("cookie is not utf-8: {}", e)))
+    }
 
-        Ok(parsed_response)
+    fn get_channel(&self, channel_name: &str, channel_id: &str) -> String {
+        if !channel_name.is_empty() {
+            return channel_name.to_string();
+        }
+        let channel = self
+            .cache
+            .get_channel(&self.client.team, channel_id)
+            .unwrap_or_else(|| crate::model::domain::Channel {
+                id: channel_id.to_string(),
+                name: channel_id.to_string(),
+                is_channel: true,
+            });
+        channel.name
+    }
+
+    /// Looks for an API token in `token_file`, then `SLACK_TOKEN`, then the keyring
+    /// entry for `team`. `None` means cookie auth should be used instead.
+    fn token(team: &str, token_file: Option<&Path>) -> Result<Option<String>> {
+        let env_token = std::env::var(TOKEN_ENV_VAR).ok().filter(|x| !x.is_empty());
+        let keyring_token = || Entry::new(TOKEN_KEYRING_SERVICE, team)?.get_password();
+        let token = if let Some(path) = token_file {
+            log::info!("using token from {}", path.display());
+            std::fs::read_to_string(path).map_err(|e| {
+                Error::Auth(format!("could not read token file {}: {}", path.display(), e))
+            })?
+        } else if let Some(token) = env_token {
+            log::info!("using token from {}", TOKEN_ENV_VAR);
+            token
+        } else if let Ok(token) = keyring_token() {
+            log::info!("using token from keyring");
+            token
+        } else {
+            return Ok(None);
+        };
+        let token = token.trim().to_string();
+        if !token.starts_with("xoxp-") && !token.starts_with("xoxb-") {
+            return Err(Error::Auth(
+                "unsupported token, expected a user (xoxp-) or bot (xoxb-) token".into(),
+            ));
+        }
+        Ok(Some(token))
     }
 
     pub fn generate_random_name() -> String {
         use fake::faker::name::raw::Name;
         match rand::thread_rng().gen_range(1..5) {
-            1 => return Name(fake::locales::FR_FR).fake(),
-            2 => return Name(fake::locales::PT_BR).fake(),
-            _ => return Name(fake::locales::EN).fake(),
+            1 => Name(fake::locales::FR_FR).fake(),
+            2 => Name(fake::locales::PT_BR).fake(),
+            _ => Name(fake::locales::EN).fake(),
             // 4=>{return Name(fake::locales::ZH_CN).fake()},
             // _=>{return Name(fake::locales::AR_SA).fake()},
         }
     }
 
-    pub fn sync(&self) {
-        self.sync_channels();
-        self.sync_users();
+    pub fn sync(&self) -> Result<()> {
+        // users first so DM channels can be named after the other user
+        self.sync_users()?;
+        self.sync_channels()
     }
 
-    pub fn send(&self, msg: String){
+    pub fn send(&self, msg: String) -> Result<()> {
         let mut body: HashMap<&str, &str>=HashMap::new();
         body.insert("channel", "D084TR3F18X");
         body.insert("text", msg.as_str());
         println!("sending message {:?}", body);
         let res = self
+            .client
             .post_api::<HashMap<String, Value>, HashMap<&str,&str>>(
                 "chat.postMessage",
                 collection! {},
                 body,
                 true,
-            )
-            .unwrap();
+            )?;
         log::info!("send message {:?}", res);
+        Ok(())
     }
 
-    pub fn sync_users(&self) {
+    pub fn sync_users(&self) -> Result<()> {
         use crate::model::domain::User;
         use crate::model::users as model;
-        let users = self
-            .api::<model::Root>("users.list", collection! {}, true)
-            .unwrap();
-        log::info!("syncing users {:?}", users);
-        let dom_users: Vec<User> = users
-            .members
-            .iter()
-            .map(|m| User {
+        let mut dom_users: Vec<User> = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let mut params: HashMap<&str, &str> = collection! {"limit"=>"200"};
+            if !cursor.is_empty() {
+                params.insert("cursor", &cursor);
+            }
+            let users = self.client.api::<model::Root>("users.list", params, true)?;
+            log::debug!("syncing users {:?}", users);
+            dom_users.extend(users.members.iter().map(|m| User {
                 id: m.id.clone(),
                 name: m.name.clone(),
-                email: m.profile.email.clone()
-            })
-            .collect();
-        self.cache
-            .sync_users(self.team.as_ref().unwrap(), dom_users)
-            .unwrap();
+                email: m.profile.email.clone(),
+                tz: m.tz.clone(),
+            }));
+            progress("users", dom_users.len());
+            cursor = users.response_metadata.next_cursor;
+            if cursor.is_empty() {
+                break;
+            }
+        }
+        progress_done("users", dom_users.len());
+        self.cache.sync_users(&self.client.team, dom_users)
     }
 
-    pub fn sync_channels(&self) {
+    pub fn sync_channels(&self) -> Result<()> {
         use crate::model::channels as model;
         use crate::model::domain::Channel;
 
-        let channels = self
-            .api::<model::Root>(
-                "conversations.list",
-                // collection! {"types"=>"public_channel,private_channel,mpim,im"},
-                collection! {"types"=>"public_channel,private_channel", "limit"=>"1000"},
-                true,
-            )
-            .unwrap();
+        let mut dom_channels: Vec<Channel> = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let mut params: HashMap<&str, &str> =
+                collection! {"types"=>"public_channel,private_channel,mpim,im", "limit"=>"1000"};
+            if !cursor.is_empty() {
+                params.insert("cursor", &cursor);
+            }
+            let channels = self.client.api::<model::Root>("conversations.list", params, true)?;
 
-        log::info!("syncing channels {:?}", channels);
+            log::debug!("syncing channels {:?}", channels);
 
-        let dom_channels: Vec<Channel> = channels
-            .channels
-            .iter()
-            .map(|c| crate::model::domain::Channel {
+            dom_channels.extend(channels.channels.iter().map(|c| Channel {
                 id: c.id.clone(),
-                name: c.name.clone(),
+                name: self.conversation_name(c),
                 is_channel: c.is_channel,
-            })
-            .collect();
+            }));
+            progress("channels", dom_channels.len());
+            cursor = channels.response_metadata.next_cursor;
+            if cursor.is_empty() {
+                break;
+            }
+        }
+        progress_done("channels", dom_channels.len());
 
-        self.cache
-            .sync_channels(self.team.as_ref().unwrap(), dom_channels)
-            .unwrap();
+        self.cache.sync_channels(&self.client.team, dom_channels)
+    }
+
+    /// DMs have no name of their own, so they are named `@user` after the other
+    /// member, and MPIMs `@a, @b, @c` from the handles Slack puts in `mpdm-a--b--c-1`.
+    fn conversation_name(&self, channel: &crate::model::channels::Channel) -> String {
+        if channel.is_im {
+            let user = self
+                .cache
+                .get_user(&self.client.team, &channel.user)
+                .map(|u| u.name)
+                .unwrap_or_else(|| channel.user.clone());
+            return format!("@{}", user);
+        }
+        if channel.is_mpim {
+            let members = channel
+                .name
+                .strip_prefix("mpdm-")
+                .map(|x| x.trim_end_matches(|c: char| c.is_ascii_digit()))
+                .map(|x| x.strip_suffix('-').unwrap_or(x))
+                .unwrap_or(&channel.name);
+            return members.split("--").map(|m| format!("@{}", m)).join(", ");
+        }
+        channel.name.clone()
     }
 
-    pub fn search(&mut self, keyword: &str, count: u32) {
+    pub fn search(&mut self, keyword: &str, count: u32) -> Result<()> {
         use crate::model::search as model;
-        let items = self
-            .api::<model::Root>(
-                "search.messages",
-                collection! {"query"=> keyword, "count"=> &count.to_string()},
-                true,
-            )
-            .unwrap();
+        let items = self.client.api::<model::Root>(
+            "search.messages",
+            collection! {"query"=> keyword, "count"=> &count.to_string()},
+            true,
+        )?;
 
-        items
+        let matches = items
             .messages
             .matches
             .into_iter()
             .filter(|m| !m.channel.is_mpim)
-            .sorted_by(|a, b| {
-                let a_ts = a.ts.parse::<f64>().unwrap_or(f64::MIN);
-                let b_ts = b.ts.parse::<f64>().unwrap_or(f64::MIN);
-                b_ts.partial_cmp(&a_ts).unwrap()
-            })
-            .for_each(|m| {
+            .sorted_by(|a, b| time::by_ts(&b.ts, &a.ts));
+        if self.format != Format::Text {
+            let records: Vec<Record> = matches
+                .map(|m| Record {
+                    time: crate::time::iso8601(&m.ts),
+                    user_name: self.known_user_name(&m.user, Some(&m.username)),
+                    channel: self.get_channel(&m.channel.name, &m.channel.id),
+                    // replies only say which thread they're in through the permalink
+                    thread_ts: crate::link::parse(&m.permalink)
+                        .ok()
+                        .and_then(|link| link.thread_ts),
+                    ts: m.ts,
+                    user_id: m.user,
+                    channel_id: m.channel.id,
+                    text: mrkdwn::render(&m.text, mrkdwn::Mode::Plain, &mut KnownNames(self)),
+                    permalink: m.permalink,
+                })
+                .collect();
+            return output::write_records(self.format, &records);
+        }
+        matches.for_each(|m| {
                 let formatted_text = self.format_text(m.text);
                 let user_name = self.get_user_name(m.user);
                 println!(
@@ -432,23 +975,19 @@ impl Slack {
                     self.highlight_keyword(formatted_text.as_ref(), keyword)
                 );
             });
-    }
-
-    pub fn thread(&mut self, channel: &str, ts: &str) -> Result<(), Box<dyn Error>> {
-        let messages=self.thread_messages(channel, ts)?;
-        self.print_messages(messages.as_ref());
         Ok(())
     }
 
-    fn thread_messages(&mut self, channel: &str, ts: &str) -> Result<Vec<Message>, Box<dyn Error>>{
-        use crate::model::replies as model;
-        let res = self.api::<model::Root>(
-            "conversations.replies",
-            collection! {"channel"=> channel, "ts"=>ts, "limit"=>"100", "inclusive"=>"true"},
-            true,
-        )?;
-        // self.print_messages(res.messages.as_ref());
-        Ok(res.messages)
+    pub fn thread(&mut self, channel: &str, ts: &str, count: Option<usize>) -> Result<()> {
+        let thread = self.client.thread_messages(channel, ts, count)?;
+        self.print_messages(channel, thread.messages.as_ref())?;
+        if thread.has_more {
+            eprintln!(
+                "showing the first {} messages of the thread, raise --count for more",
+                thread.messages.len()
+            );
+        }
+        Ok(())
     }
 
     pub fn highlight_keyword(&mut self, text: &str, keyword: &str) -> String {
@@ -459,132 +998,254 @@ impl Slack {
         text.to_string()
     }
 
+    /// Renders mrkdwn styled for a terminal, or plain when colors are off.
     fn format_text(&mut self, text: String) -> String {
-        let new_line_replacer = Regex::new(r"\n{2,}").unwrap();
-        let user_placeholder_replacer = Regex::new(r"<@([^>]+)>").unwrap();
-        let gt_replacer = Regex::new(r"&gt;.*\n").unwrap();
-        let text = new_line_replacer.replace_all(&text, "\n");
-        let text = gt_replacer.replace_all(&text, |caps: &regex::Captures| {
-            caps.get(0)
-                .unwrap()
-                .as_str()
-                .replace("&gt;", "|")
-                .blue()
-                .bold()
-                .italic()
-                .to_string()
-            // caps.get(0).unwrap().as_str().replace("&gt;", "|").black().on_white().bold().italic().to_string()
-        });
-        let text = user_placeholder_replacer.replace_all(&text, |caps: &regex::Captures| {
-            let user_id = caps.get(1).unwrap().as_str().to_string();
-            let user_name = self.get_user_name(user_id);
-            format!("@{}", user_name.italic().bold().yellow())
-        });
-        text.to_string()
+        let mode = if colored::control::SHOULD_COLORIZE.should_colorize() {
+            mrkdwn::Mode::Styled
+        } else {
+            mrkdwn::Mode::Plain
+        };
+        mrkdwn::render(&text, mode, self)
+    }
+
+    /// Who posted `message`: the user, else the name a bot posted under.
+    fn author(&mut self, message: &Message) -> String {
+        if !message.user.is_empty() {
+            return self.get_user_name(message.user.clone());
+        }
+        self.known_author(message)
+    }
+
+    /// `author` for output that is kept, which must not make a name up: the
+    /// cache, else the name Slack sent along, else the id.
+    fn known_author(&self, message: &Message) -> String {
+        if message.user.is_empty() {
+            return message
+                .username
+                .clone()
+                .or_else(|| message.bot_id.clone())
+                .unwrap_or_else(|| "unknown".to_string());
+        }
+        self.known_user_name(&message.user, message.username.as_deref())
+    }
+
+    fn known_user_name(&self, user_id: &str, username: Option<&str>) -> String {
+        self.cache
+            .get_user(&self.client.team, user_id)
+            .map(|user| user.name)
+            .or_else(|| username.filter(|name| !name.is_empty()).map(str::to_string))
+            .unwrap_or_else(|| user_id.to_string())
     }
 
     fn get_user_name(&mut self, user_id: String) -> String {
         let user = self
             .cache
-            .get_user(self.team.as_ref().unwrap(), &user_id)
+            .get_user(&self.client.team, &user_id)
             .unwrap_or_else(|| {
                 let name=self
                     .user_map
                     .entry(user_id.clone())
-                    .or_insert_with(|| Slack::generate_random_name())
+                    .or_insert_with(Slack::generate_random_name)
                     .to_string();
                 let email=format!("{name}@gmail.com");
                 User {
-                    name: name,
+                    name,
                     id: user_id.clone(),
-                    email: email
+                    email,
+                    ..User::default()
                 }
             });
         user.name
     }
 
-    fn print_messages(&mut self, messages: &Vec<crate::model::message::Message>) {
+    fn print_messages(
+        &mut self,
+        channel: &str,
+        messages: &[crate::model::message::Message],
+    ) -> Result<()> {
+        if self.format != Format::Text {
+            let records = self.records(channel, messages);
+            return output::write_records(self.format, &records);
+        }
+        let mut day = String::new();
         messages
             .iter()
-            .filter(|m| !m.text.is_empty())
-            .sorted_by(|a, b| {
-                let a_ts = a.ts.parse::<f64>().unwrap_or(f64::MIN);
-                let b_ts = b.ts.parse::<f64>().unwrap_or(f64::MIN);
-                a_ts.partial_cmp(&b_ts).unwrap()
-            })
-            .for_each(move |m| {
-                let user = m.user.clone();
-                let user_name = self.get_user_name(user.clone());
-                let text = self.format_text(m.text.clone());
-                println!("{}: {}\n", user_name.italic().bold().yellow(), text);
+            .sorted_by(|a, b| time::by_ts(&a.ts, &b.ts))
+            .map(|m| (m, content::mrkdwn(m)))
+            .filter(|(_, text)| !text.is_empty())
+            .for_each(move |(m, text)| {
+                if self.clock.date_headers {
+                    let date = self.clock.date(&m.ts);
+                    if date != day {
+                        println!("{}\n", format!("── {} ──", date).bold());
+                        day = date;
+                    }
+                }
+                let user_name = self.author(m);
+                let text = self.format_text(text);
+                let edited = match m.edited {
+                    Some(_) => format!(" {}", "(edited)".dimmed()),
+                    None => String::new(),
+                };
+                println!(
+                    "{} {}: {}{}",
+                    self.clock.time(&m.ts).dimmed(),
+                    user_name.italic().bold().yellow(),
+                    text,
+                    edited
+                );
+                if let Some(replies) = m.reply_count.filter(|count| *count > 0) {
+                    let summary = self.replies_summary(replies, m.latest_reply.as_deref());
+                    println!("  {}", summary.dimmed());
+                }
+                println!();
             });
+        Ok(())
+    }
+
+    /// `2 replies, last 2026-10-01 09:00` under a thread's first message.
+    fn replies_summary(&self, count: u32, latest: Option<&str>) -> String {
+        let mut summary = match count {
+            1 => "1 reply".to_string(),
+            count => format!("{} replies", count),
+        };
+        if let Some(latest) = latest {
+            // the last reply may be days later, so keep its date
+            let clock = time::Clock {
+                date_headers: false,
+                ..self.clock
+            };
+            summary.push_str(&format!(", last {}", clock.time(latest)));
+        }
+        summary
+    }
+
+    /// `messages` oldest first as records for the machine-readable formats,
+    /// skipping the same empty messages the text output does.
+    fn records(&self, channel: &str, messages: &[Message]) -> Vec<Record> {
+        let channel_name = self.get_channel("", channel);
+        messages
+            .iter()
+            .sorted_by(|a, b| time::by_ts(&a.ts, &b.ts))
+            .map(|m| (m, content::mrkdwn(m)))
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(m, text)| Record {
+                ts: m.ts.clone(),
+                time: crate::time::iso8601(&m.ts),
+                user_id: m.user.clone(),
+                user_name: self.known_author(m),
+                channel_id: channel.to_string(),
+                channel: channel_name.clone(),
+                text: mrkdwn::render(&text, mrkdwn::Mode::Plain, &mut KnownNames(self)),
+                thread_ts: m.thread_ts.clone(),
+                permalink: crate::link::permalink(
+                    &self.client.team,
+                    channel,
+                    &m.ts,
+                    m.thread_ts.as_deref(),
+                ),
+            })
+            .collect()
+    }
+
+    /// Looks up a channel id by name in the cache filled by `sync`.
+    pub fn channel_id(&self, name: &str) -> Result<String> {
+        self.cache.get_channel_id(&self.client.team, name).ok_or_else(|| {
+            Error::Input(format!(
+                "no channel #{} in {}, run `sync --team {}` first if it is new",
+                name, self.client.team, self.client.team
+            ))
+        })
     }
 
     pub fn read_threaded(&mut self,
         channel: &str,
-        start_time: &str,
+        range: &Range,
         count: u32,
-    )-> Result<(), Box<dyn Error>> {
-        let mut messages=Vec::new();
-        let mut cursor_empty_already = false;
-        let mut start_time=start_time.to_string().clone();
-        self.read_paginated(&mut messages,channel, &mut start_time, count, true, "", &mut cursor_empty_already)?;
+        limit: Option<usize>,
+        concurrency: usize,
+    )-> Result<()> {
+        let thread_messages = self.threads(channel, range, count, limit, concurrency)?;
+        if self.format == Format::Text {
+            println!("total unique threads {}",thread_messages.len());
+        }
+        let threads = thread_messages.iter().filter(|thread| thread.len() > 1);
+        if self.format != Format::Text {
+            // one batch, so json output stays a single array
+            let records: Vec<Record> = threads
+                .flat_map(|thread| self.records(channel, thread))
+                .collect();
+            return output::write_records(self.format, &records);
+        }
+        for thread in threads {
+            self.print_messages(channel, thread)?;
+            println!("\n\n\n\n");
+        }
+        Ok(())
+
+    }
+
+    /// Every thread with a message in `range`, each with all its replies,
+    /// oldest thread first.
+    pub fn threads(
+        &self,
+        channel: &str,
+        range: &Range,
+        count: u32,
+        limit: Option<usize>,
+        concurrency: usize,
+    ) -> Result<Vec<Vec<Message>>> {
+        let messages = self.messages(channel, range, count, limit)?;
         let mut threaded_ts_set: HashSet<String>=HashSet::new();
-        messages.iter().filter(|m|m.thread_ts.is_some() || (m.thread_ts.is_none() && m.ts!="") && !m.text.contains("has joined the channel")).for_each(|m|{
+        messages.iter().filter(|m|m.thread_ts.is_some() || (m.thread_ts.is_none() && !m.ts.is_empty()) && !m.text.contains("has joined the channel")).for_each(|m|{
             if m.thread_ts.is_none(){
                 threaded_ts_set.insert(m.ts.clone());
             }else{
                 threaded_ts_set.insert(m.thread_ts.clone().unwrap());
             }
         });
-        println!("total unique threads {}",threaded_ts_set.len());
-        let mut thread_messages: Vec<Vec<Message>>=Vec::new();
-        
-        // threaded_ts_set.iter().for_each(|ts|{
-        //     let mut thread=Vec::new();
-        //     messages.iter().filter(|m| m.thread_ts.is_some() && m.thread_ts.clone().unwrap()==*ts && m.ts!=*ts).for_each(|m|{
-        //         thread.push(m.clone());
-        //     });
-        //     thread_messages.push(thread);
-        // });
-        // let thread_messages = Arc::new(Mutex::new(Vec::new()));
-        // let pool = ThreadPool::new(10);
-
-        // for ts in threaded_ts_set {
-        //     let channel = channel.to_string();
-        //     let thread_messages = Arc::clone(&thread_messages);
-        //     let self_clone = self.clone(); // Assuming Slack implements Clone
-
-        //     pool.execute(move || {
-        //         let thread = self_clone.thread_messages(&channel, &ts).unwrap();
-        //         let mut thread_messages = thread_messages.lock().unwrap();
-        //         thread_messages.push(thread);
-        //     });
-        // }
-        // pool.join();
-        // let thread_messages = Arc::try_unwrap(thread_messages).unwrap().into_inner().unwrap();
-
-        threaded_ts_set.iter().for_each(|ts|{
-            let thread=self.thread_messages(channel, ts).unwrap();
-            thread_messages.push(thread);
-        });
-        thread_messages.iter().for_each(|thread|{
-            if thread.len()<=1{
-                return;
-            }
-            self.print_messages(thread);
-            println!("\n\n\n\n");
-        });
-        Ok(())
+        // oldest thread first, whichever worker finishes first
+        let threads: Vec<String> = threaded_ts_set
+            .into_iter()
+            .sorted_by(|a, b| time::by_ts(a, b))
+            .collect();
+        self.fetch_threads(channel, &threads, concurrency)
+    }
 
+    /// Fetches each thread's replies on up to `concurrency` workers sharing
+    /// the client and its rate limits. Threads come back in the order given.
+    fn fetch_threads(
+        &self,
+        channel: &str,
+        threads: &[String],
+        concurrency: usize,
+    ) -> Result<Vec<Vec<Message>>> {
+        let pool = ThreadPool::new(concurrency.max(1));
+        let (sender, receiver) = mpsc::channel();
+        for (i, ts) in threads.iter().enumerate() {
+            let client = Arc::clone(&self.client);
+            let channel = channel.to_string();
+            let ts = ts.clone();
+            let sender = sender.clone();
+            pool.execute(move || {
+                // the receiver is kept until every job has sent
+                let thread = client.thread_messages(&channel, &ts, None);
+                let _ = sender.send((i, thread.map(|thread| thread.messages)));
+            });
+        }
+        drop(sender);
+        let mut results: Vec<(usize, Result<Vec<Message>>)> = receiver.iter().collect();
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, thread)| thread).collect()
     }
 
     pub fn read(
         &mut self,
         channel: &str,
-        start_time: &str,
+        range: &Range,
         count: u32,
-    ) -> Result<(), Box<dyn Error>> {
+        limit: Option<usize>,
+    ) -> Result<()> {
         // use crate::model::conversations as model;
         // let res = self.api::<model::Root>(
         //     "conversations.history",
@@ -595,84 +1256,368 @@ impl Slack {
         // println!("empty thread ts {}",res.messages.iter().filter(|m|m.thread_ts == None).count());
         // // self.print_messages(&res.messages);
         // println!("cursor {}",res.response_metadata.next_cursor);
-        let mut messages=Vec::new();
-        let mut cursor_empty_already = false;
-        let mut start_time=start_time.to_string().clone();
-        self.read_paginated(&mut messages,channel, &mut start_time, count, true, "", &mut cursor_empty_already)?;
-        self.print_messages(&messages);
+        let messages = self.messages(channel, range, count, limit)?;
+        self.print_messages(channel, &messages)?;
 
         log::info!("Total Messages: {}", messages.len());
         Ok(())
     }
 
-    fn get_max_ts(&mut self, messages: &Vec<crate::model::message::Message>) -> String{
-        messages.iter().max_by(|a,b|{
-            let a_ts = a.ts.parse::<f64>().unwrap_or(f64::MIN);
-            let b_ts = b.ts.parse::<f64>().unwrap_or(f64::MIN);
-            a_ts.partial_cmp(&b_ts).unwrap()
-        }).map(|m|m.ts.clone()).unwrap_or("".to_string())
-    }
-
-    fn read_paginated(
-        &mut self,
-        messages: &mut Vec<crate::model::message::Message>,
+    /// All of `history`, newest first.
+    pub fn messages(
+        &self,
         channel: &str,
-        start_time: &mut String,
+        range: &Range,
         count: u32,
-        first_time: bool,
-        cursor: &str,
-        cursor_empty_already: &mut bool,
-    ) -> Result<(), Box<dyn Error>> {
-        log::info!(
-            "current cursor-> {}, first time-> {}, count-> {}, start_time-> {}, cursor_empty_already-> {}",
-            cursor,
-            first_time,
-            count,
-            start_time,
-            cursor_empty_already
-        );
-        if cursor == "" && !first_time && *cursor_empty_already{
-            // println!("early return");
-            return Ok(());
+        limit: Option<usize>,
+    ) -> Result<Vec<Message>> {
+        self.history(channel, range, count, limit)
+            .flatten_ok()
+            .collect()
+    }
+
+    /// A thread's messages, the first one included, stopping after `count`.
+    pub fn replies(&self, channel: &str, ts: &str, count: Option<usize>) -> Result<Vec<Message>> {
+        Ok(self.client.thread_messages(channel, ts, count)?.messages)
+    }
+
+    /// Lays `threads` out for `export`: each thread's first message with the
+    /// rest as its replies, users resolved and empty messages dropped.
+    pub fn transcript(&mut self, channel: &str, threads: &[Vec<Message>]) -> Transcript {
+        let mut messages: Vec<export::Entry> = Vec::new();
+        for thread in threads {
+            let mut entries = self.entries(channel, thread).into_iter();
+            if let Some(mut parent) = entries.next() {
+                parent.replies = entries.collect();
+                messages.push(parent);
+            }
+        }
+        messages.sort_by(|a, b| time::by_ts(&a.ts, &b.ts));
+        Transcript {
+            channel: self.get_channel("", channel),
+            messages,
+        }
+    }
+
+    fn entries(&self, channel: &str, messages: &[Message]) -> Vec<export::Entry> {
+        static MENTION: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"<@([A-Z0-9]+)>").unwrap());
+        messages
+            .iter()
+            .sorted_by(|a, b| time::by_ts(&a.ts, &b.ts))
+            .map(|m| (m, content::mrkdwn(m)))
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(m, text)| export::Entry {
+                author: self.known_author(m),
+                ts: m.ts.clone(),
+                // mentions carry the name the way links carry their label
+                text: MENTION
+                    .replace_all(&text, |caps: &regex::Captures| {
+                        let name = self.known_user_name(&caps[1], None);
+                        format!("<@{}|{}>", &caps[1], name.replace('>', "&gt;"))
+                    })
+                    .into_owned(),
+                permalink: crate::link::permalink(
+                    &self.client.team,
+                    channel,
+                    &m.ts,
+                    m.thread_ts.as_deref(),
+                ),
+                replies: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Pages of `conversations.history` in `range`. With nothing to start
+    /// from, stops at the newest page unless a `limit` is given.
+    pub fn history<'a>(
+        &'a self,
+        channel: &'a str,
+        range: &'a Range,
+        page_size: u32,
+        limit: Option<usize>,
+    ) -> History<'a> {
+        History {
+            client: &self.client,
+            channel,
+            range,
+            page_size: page_size.max(1) as usize,
+            cursor: None,
+            remaining: limit.or_else(|| range.oldest.is_none().then_some(page_size as usize)),
+            done: false,
+        }
+    }
+}
+
+/// Pages of a channel's history, newest first. Ends when Slack has no more
+/// or once `remaining` messages have been handed out.
+pub struct History<'a> {
+    client: &'a Client,
+    channel: &'a str,
+    range: &'a Range,
+    page_size: usize,
+    cursor: Option<String>,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+impl mrkdwn::Names for Slack {
+    fn user(&mut self, id: &str) -> String {
+        self.get_user_name(id.to_string())
+    }
+
+    fn channel(&mut self, id: &str) -> String {
+        self.get_channel("", id)
+    }
+}
+
+/// Names for output that is kept, which go through `known_user_name` rather
+/// than being made up.
+struct KnownNames<'a>(&'a Slack);
+
+impl mrkdwn::Names for KnownNames<'_> {
+    fn user(&mut self, id: &str) -> String {
+        self.0.known_user_name(id, None)
+    }
+
+    fn channel(&mut self, id: &str) -> String {
+        self.0.get_channel("", id)
+    }
+}
+
+impl Iterator for History<'_> {
+    type Item = Result<Vec<Message>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == Some(0) {
+            return None;
         }
         use crate::model::conversations as model;
-        let count_str = format!("{}", count);
-        let res = self.api::<model::Root>(
-            "conversations.history",
-            if cursor!=""{
-                collection! {"channel"=> channel, "limit"=> count_str.as_ref(), "cursor"=>cursor}
-            } else{
-                 collection! {"channel"=> channel, "oldest"=>start_time,"limit"=> count_str.as_ref()}
-            },
-            false,
-        )?;
-        if res.messages.len()==0{
-            *cursor_empty_already=true;
-            return Ok(());
+        let page_size = self
+            .remaining
+            .map_or(self.page_size, |remaining| remaining.min(self.page_size))
+            .to_string();
+        let mut params: HashMap<&str, &str> =
+            collection! {"channel"=> self.channel, "limit"=> page_size.as_str()};
+        if let Some(oldest) = &self.range.oldest {
+            // Slack leaves the message at `oldest` out unless asked, and that
+            // is the linked one when reading from a permalink
+            params.insert("oldest", oldest);
+            params.insert("inclusive", "true");
         }
-        if res.response_metadata.next_cursor==""{
-            if self.get_max_ts(messages) == self.get_max_ts(res.messages.as_ref()){
-                *cursor_empty_already=true;
-            }else{
-                *start_time=self.get_max_ts(messages);
+        if let Some(latest) = &self.range.latest {
+            params.insert("latest", latest);
+        }
+        if let Some(cursor) = &self.cursor {
+            params.insert("cursor", cursor);
+        }
+        let res = self
+            .client
+            .api::<model::Root>("conversations.history", params, false);
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
             }
+        };
+        let next_cursor = res.response_metadata.next_cursor;
+        self.done = !res.has_more || next_cursor.is_empty();
+        self.cursor = Some(next_cursor);
+        let mut messages = res.messages;
+        if let Some(remaining) = &mut self.remaining {
+            messages.truncate(*remaining);
+            *remaining -= messages.len();
+        }
+        log::info!("fetched {} messages, more: {}", messages.len(), !self.done);
+        Some(Ok(messages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    /// Answers with canned responses in order.
+    struct Canned(Mutex<VecDeque<Response>>);
+
+    impl Transport for Canned {
+        fn send(&self, _request: &Request) -> Result<Response> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no response left"))
+        }
+    }
+
+    fn response(status: u16, retry_after: Option<u64>) -> Response {
+        Response {
+            status,
+            retry_after,
+            body: br#"{"ok": true}"#.to_vec(),
+        }
+    }
+
+    fn client(responses: Vec<Response>) -> Client {
+        Client {
+            auth: None,
+            transport: Box::new(Canned(Mutex::new(responses.into()))),
+            base_url: None,
+            team: "mock".into(),
+            limiter: RateLimiter::new(),
+        }
+    }
+
+    fn request(method: Method) -> Request {
+        Request {
+            method,
+            url: "https://mock.slack.com/api/chat.postMessage"
+                .parse()
+                .unwrap(),
+            headers: HeaderMap::new(),
+            body: Body::Empty,
         }
-        // println!("next cursor-> {}", res.response_metadata.next_cursor);
-        log::info!("next cursor-> {}", res.response_metadata.next_cursor);
-        log::info!(
-            "empty thread ts {}",
-            res.messages.iter().filter(|m| m.thread_ts == None && m.ts=="").count()
+    }
+
+    #[test]
+    fn searches_native_flatpak_and_snap_config_dirs() {
+        let dirs = Slack::config_dirs("/home/ann", None, false);
+        assert_eq!(
+            dirs,
+            [
+                "/home/ann/.config/Slack",
+                "/home/ann/.var/app/com.slack.Slack/config/Slack",
+                "/home/ann/snap/slack/current/.config/Slack",
+            ]
         );
-        messages.extend(res.messages);
-        self.read_paginated(
-            messages,
-            channel,
-            start_time,
-            count,
-            false,
-            res.response_metadata.next_cursor.as_ref(),
-            cursor_empty_already,
-        )?;
-        Ok(())
+        let xdg = Slack::config_dirs("/home/ann", Some("/xdg".to_string()), false);
+        assert_eq!(xdg[0], "/xdg/Slack");
+        let mac = Slack::config_dirs("/Users/ann", None, true);
+        assert_eq!(mac[0], "/Users/ann/Library/Application Support/Slack");
+
+        let cases = [
+            (
+                vec![
+                    "/home/ann/.config/Slack/Cookies",
+                    "/home/ann/snap/slack/current/.config/Slack/Cookies",
+                ],
+                "/home/ann/.config/Slack/Cookies",
+            ),
+            (
+                vec!["/home/ann/.var/app/com.slack.Slack/config/Slack/Network/Cookies"],
+                "/home/ann/.var/app/com.slack.Slack/config/Slack/Network/Cookies",
+            ),
+            (
+                vec!["/home/ann/snap/slack/current/.config/Slack/Cookies"],
+                "/home/ann/snap/slack/current/.config/Slack/Cookies",
+            ),
+        ];
+        for (existing, expected) in cases {
+            let found = Slack::cookie_db_path(&dirs, |path| existing.contains(&path));
+            assert_eq!(found.unwrap(), expected, "{:?}", existing);
+        }
+
+        let error = Slack::cookie_db_path(&dirs, |_| false)
+            .unwrap_err()
+            .to_string();
+        for dir in &dirs {
+            assert!(error.contains(dir.as_str()), "{}", error);
+        }
+    }
+
+    #[test]
+    fn picks_the_cookie_password_by_version() {
+        let stored = || Ok(b"stored".to_vec());
+        let missing = || Err(Error::Auth("no entry".into()));
+        // (version, linux, stored password, expected)
+        let cases = [
+            ("v10", true, true, Some("peanuts")),
+            ("v10", true, false, Some("peanuts")),
+            ("v11", true, true, Some("stored")),
+            ("v11", true, false, Some("peanuts")),
+            ("v10", false, true, Some("stored")),
+            ("v10", false, false, None),
+        ];
+        for (version, linux, has_stored, expected) in cases {
+            let password = if has_stored {
+                Slack::choose_cookie_password(version.as_bytes(), linux, stored)
+            } else {
+                Slack::choose_cookie_password(version.as_bytes(), linux, missing)
+            };
+            assert_eq!(
+                password.ok().as_deref(),
+                expected.map(str::as_bytes),
+                "{} linux={} stored={}",
+                version,
+                linux,
+                has_stored
+            );
+        }
+    }
+
+    #[test]
+    fn names_dms_and_group_dms_after_their_members() {
+        let cache = crate::cache::sqlite::InMemoryCache::in_memory().unwrap();
+        let bob = User {
+            id: "U2".to_string(),
+            name: "bob".to_string(),
+            ..User::default()
+        };
+        cache.sync_users("mock", vec![bob]).unwrap();
+        let slack = Slack {
+            client: Arc::new(client(Vec::new())),
+            cache,
+            user_map: HashMap::new(),
+            format: Format::Text,
+            clock: time::Clock::default(),
+        };
+        let cases = [
+            (r#"{"id": "D1", "is_im": true, "user": "U2"}"#, "@bob"),
+            // not synced yet, so the id is all there is
+            (r#"{"id": "D2", "is_im": true, "user": "U9"}"#, "@U9"),
+            (
+                r#"{"id": "G1", "name": "mpdm-alice--bob--carol-1", "is_mpim": true}"#,
+                "@alice, @bob, @carol",
+            ),
+            (
+                r#"{"id": "C1", "name": "general", "is_channel": true}"#,
+                "general",
+            ),
+        ];
+        for (channel, expected) in cases {
+            let channel = serde_json::from_str(channel).unwrap();
+            assert_eq!(slack.conversation_name(&channel), expected, "{:?}", channel);
+        }
+    }
+
+    #[test]
+    fn waits_out_retry_after() {
+        let client = client(vec![response(429, Some(1)), response(200, None)]);
+        let start = Instant::now();
+        let res = client
+            .send_with_retry("chat.postMessage", &request(Method::Post))
+            .unwrap();
+        assert_eq!(res.status, 200);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn retries_server_errors_only_for_gets() {
+        let get = client(vec![response(503, None), response(200, None)]);
+        let res = get
+            .send_with_retry("conversations.history", &request(Method::Get))
+            .unwrap();
+        assert_eq!(res.status, 200);
+
+        let post = client(vec![response(503, None), response(200, None)]);
+        let res = post
+            .send_with_retry("chat.postMessage", &request(Method::Post))
+            .unwrap();
+        assert_eq!(res.status, 503);
     }
 }
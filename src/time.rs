@@ -0,0 +1,265 @@
+use crate::error::{Error, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use clap::ValueEnum;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::sync::LazyLock;
+
+const ACCEPTED: &str =
+    "expected a duration like 90m, 2h or 3d, a date like 2026-10-01 or '2026-10-01 09:00', or a slack timestamp";
+
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Turns a `--since`/`--until` value into a Slack timestamp for `oldest` and
+/// `latest`. Durations count back from now, and dates without an offset are
+/// read in the local timezone.
+pub fn parse_time(value: &str) -> Result<String> {
+    static TIMESTAMP: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\d+)(?:\.(\d{1,6}))?$").unwrap());
+    static DURATION: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\d+)\s*(s|m|h|d|w)$").unwrap());
+    let value = value.trim();
+    let invalid = || Error::Input(format!("invalid time {:?}: {}", value, ACCEPTED));
+
+    if let Some(ts) = TIMESTAMP.captures(value) {
+        let micros = ts.get(2).map_or("", |m| m.as_str());
+        return Ok(format!("{}.{:0<6}", &ts[1], micros));
+    }
+
+    if let Some(duration) = DURATION.captures(value) {
+        let n: i64 = duration[1].parse().map_err(|_| invalid())?;
+        let duration = match &duration[2] {
+            "s" => Duration::try_seconds(n),
+            "m" => Duration::try_minutes(n),
+            "h" => Duration::try_hours(n),
+            "d" => Duration::try_days(n),
+            _ => Duration::try_weeks(n),
+        }
+        .ok_or_else(invalid)?;
+        return Ok(slack_ts(Local::now() - duration));
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(slack_ts(time));
+    }
+    let naive = DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(invalid)?;
+    // earliest() settles the ambiguous hour when clocks go back
+    let time = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| Error::Input(format!("{:?} does not exist in the local timezone", value)))?;
+    Ok(slack_ts(time))
+}
+
+/// Orders Slack timestamps oldest first, with any that don't parse before the rest.
+pub fn by_ts(a: &str, b: &str) -> Ordering {
+    let ts = |ts: &str| ts.parse::<f64>().unwrap_or(f64::MIN);
+    ts(a).total_cmp(&ts(b))
+}
+
+/// A Slack timestamp as an ISO 8601 time in UTC, or empty if it isn't one.
+pub fn iso8601(ts: &str) -> String {
+    utc(ts)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// A Slack timestamp to the minute for transcripts, e.g. `2026-10-01 09:00 UTC`.
+pub fn display(ts: &str) -> String {
+    utc(ts)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+/// How `print_messages` shows message times.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum TimeFormat {
+    /// Date and time to the minute, e.g. 2026-10-01 09:00
+    #[default]
+    Local,
+    /// RFC 3339 with the zone's offset
+    Iso,
+    /// How long ago, e.g. 5m ago
+    Relative,
+}
+
+/// The timezone times are shown in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Zone {
+    #[default]
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+impl Zone {
+    /// `local`, or an IANA name like `Europe/London` as Slack profiles have.
+    pub fn parse(name: &str) -> Option<Zone> {
+        if name.eq_ignore_ascii_case("local") {
+            return Some(Zone::Local);
+        }
+        name.parse().ok().map(Zone::Named)
+    }
+}
+
+/// Formats message times in a zone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock {
+    pub format: TimeFormat,
+    pub zone: Zone,
+    /// Messages are grouped under date headers, so local times leave the date out.
+    pub date_headers: bool,
+}
+
+impl Clock {
+    pub fn time(&self, ts: &str) -> String {
+        let Some(time) = utc(ts) else {
+            return String::new();
+        };
+        match self.format {
+            TimeFormat::Relative => relative(Utc::now() - time),
+            TimeFormat::Iso => self.format(time, "%Y-%m-%dT%H:%M:%S%:z"),
+            TimeFormat::Local if self.date_headers => self.format(time, "%H:%M"),
+            TimeFormat::Local => self.format(time, "%Y-%m-%d %H:%M"),
+        }
+    }
+
+    /// The day `ts` falls on in the zone, e.g. `Tuesday, 14 November 2023`.
+    pub fn date(&self, ts: &str) -> String {
+        utc(ts)
+            .map(|time| self.format(time, "%A, %-d %B %Y"))
+            .unwrap_or_default()
+    }
+
+    fn format(&self, time: DateTime<Utc>, format: &str) -> String {
+        match self.zone {
+            Zone::Local => time.with_timezone(&Local).format(format).to_string(),
+            Zone::Named(tz) => time.with_timezone(&tz).format(format).to_string(),
+        }
+    }
+}
+
+fn relative(ago: Duration) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    match ago.num_seconds() {
+        seconds if seconds < MINUTE => "just now".to_string(),
+        seconds if seconds < HOUR => format!("{}m ago", seconds / MINUTE),
+        seconds if seconds < DAY => format!("{}h ago", seconds / HOUR),
+        seconds if seconds < 30 * DAY => format!("{}d ago", seconds / DAY),
+        seconds if seconds < 365 * DAY => format!("{}mo ago", seconds / (30 * DAY)),
+        seconds => format!("{}y ago", seconds / (365 * DAY)),
+    }
+}
+
+fn utc(ts: &str) -> Option<DateTime<Utc>> {
+    ts.split('.')
+        .next()
+        .and_then(|seconds| seconds.parse().ok())
+        .and_then(|seconds| DateTime::<Utc>::from_timestamp(seconds, 0))
+}
+
+fn slack_ts<Tz: TimeZone>(time: DateTime<Tz>) -> String {
+    format!("{}.{:06}", time.timestamp(), time.timestamp_subsec_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(value: &str) -> String {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap();
+        slack_ts(Local.from_local_datetime(&naive).earliest().unwrap())
+    }
+
+    fn seconds(ts: &str) -> i64 {
+        ts.split('.').next().unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn parses_dates_and_timestamps() {
+        let cases = [
+            ("1700000000.1", "1700000000.100000".to_string()),
+            ("1700000000.123456", "1700000000.123456".to_string()),
+            ("1700000000", "1700000000.000000".to_string()),
+            ("2026-10-01T09:00:00Z", "1790845200.000000".to_string()),
+            ("2026-10-01T09:00:00+02:00", "1790838000.000000".to_string()),
+            ("2026-10-01 09:00", local("2026-10-01 09:00")),
+            ("2026-10-01T09:00", local("2026-10-01 09:00")),
+            (" 2026-10-01 09:00:00 ", local("2026-10-01 09:00")),
+            ("2026-10-01", local("2026-10-01 00:00")),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_time(input).unwrap(), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_durations_back_from_now() {
+        let cases = [
+            ("90m", 90 * 60),
+            ("2h", 2 * 3600),
+            ("3d", 3 * 86400),
+            ("1w", 7 * 86400),
+        ];
+        for (input, ago) in cases {
+            let now = Utc::now().timestamp();
+            let ts = seconds(&parse_time(input).unwrap());
+            assert!((now - ago - ts).abs() <= 1, "{:?} gave {}", input, ts);
+        }
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        let cases = [
+            "",
+            "yesterday",
+            "2h ago",
+            "-2h",
+            "1700000000.1234567",
+            "2026-13-01",
+            "2026-10-01 25:00",
+            "99999999999999999999d",
+        ];
+        for input in cases {
+            assert!(
+                matches!(parse_time(input), Err(Error::Input(_))),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn orders_timestamps_by_value() {
+        let mut ts = [
+            "1700000100.000001",
+            "",
+            "999999999.000000",
+            "1700000100.000000",
+        ];
+        ts.sort_by(|a, b| by_ts(a, b));
+        assert_eq!(
+            ts,
+            [
+                "",
+                "999999999.000000",
+                "1700000100.000000",
+                "1700000100.000001"
+            ]
+        );
+    }
+}
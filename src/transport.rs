@@ -0,0 +1,81 @@
+use crate::error::Result;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    Empty,
+    Json(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Body,
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    /// Seconds from the `Retry-After` header, sent along with a 429.
+    pub retry_after: Option<u64>,
+    pub body: Vec<u8>,
+}
+
+/// Sends a fully built request. The Slack client owns everything above this
+/// (urls, auth, retries, decoding) so transports only move bytes.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: &Request) -> Result<Response>;
+}
+
+#[derive(Default)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let builder = match request.method {
+            Method::Get => self.client.get(request.url.as_ref()),
+            Method::Post => self.client.post(request.url.as_ref()),
+        };
+        let builder = builder.headers(request.headers.clone());
+        let builder = match &request.body {
+            Body::Empty => builder,
+            Body::Json(json) => builder
+                .header(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/json; charset=utf-8"),
+                )
+                .body(json.clone()),
+        };
+        let res = builder.send()?;
+        let status = res.status().as_u16();
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let body = res.bytes()?.to_vec();
+        Ok(Response {
+            status,
+            retry_after,
+            body,
+        })
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::{Error, Result};
+use crate::transport::{Body, Method, Request, Response, Transport};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Headers that carry credentials and are never written to disk.
+const SECRET_HEADERS: &[&str] = &["authorization", "cookie"];
+
+/// One request/response pair, stored as `<n>-<api method>.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    /// Path only, so a recording against slack.com replays against any host.
+    path: String,
+    /// Sorted, since params are built from a `HashMap`.
+    query: Vec<(String, String)>,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    json: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
+    /// The decoded JSON body, or the raw text when it isn't JSON.
+    body: Value,
+}
+
+impl RecordedRequest {
+    fn new(request: &Request) -> Self {
+        let mut query: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+        query.sort();
+        let mut headers: Vec<(String, String)> = request
+            .headers
+            .iter()
+            .filter(|(name, _)| !SECRET_HEADERS.contains(&name.as_str()))
+            .map(|(name, value)| {
+                let value = value.to_str().unwrap_or_default().to_string();
+                (name.to_string(), value)
+            })
+            .collect();
+        headers.sort();
+        let json = match &request.body {
+            Body::Empty => None,
+            Body::Json(json) => serde_json::from_str(json).ok(),
+        };
+        RecordedRequest {
+            method: match request.method {
+                Method::Get => "GET".into(),
+                Method::Post => "POST".into(),
+            },
+            path: request.url.path().to_string(),
+            query,
+            headers,
+            json,
+        }
+    }
+
+    /// Headers don't take part in matching, they're kept for whoever reads the file.
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        self.method == other.method
+            && self.path == other.path
+            && self.query == other.query
+            && self.json == other.json
+    }
+
+    fn api_method(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+}
+
+/// Passes requests through to `inner` and saves every Web API call under `dir`
+/// with its credentials stripped.
+pub struct RecordingTransport {
+    inner: Box<dyn Transport>,
+    dir: PathBuf,
+    count: AtomicUsize,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Box<dyn Transport>, dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(|e| {
+            Error::Input(format!(
+                "could not create record dir {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        Ok(RecordingTransport {
+            inner,
+            dir: dir.to_path_buf(),
+            count: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let response = self.inner.send(request)?;
+        // the team homepage scraped during cookie auth holds the session token
+        if !request.url.path().starts_with("/api/") {
+            return Ok(response);
+        }
+        let interaction = Interaction {
+            request: RecordedRequest::new(request),
+            response: RecordedResponse {
+                status: response.status,
+                retry_after: response.retry_after,
+                body: serde_json::from_slice(&response.body).unwrap_or_else(|_| {
+                    Value::String(String::from_utf8_lossy(&response.body).into_owned())
+                }),
+            },
+        };
+        let n = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+        let path = self.dir.join(format!(
+            "{:04}-{}.json",
+            n,
+            interaction.request.api_method()
+        ));
+        log::info!("recording {}", path.display());
+        let json = serde_json::to_string_pretty(&interaction)?;
+        fs::write(&path, json)
+            .map_err(|e| Error::Input(format!("could not write {}: {}", path.display(), e)))?;
+        Ok(response)
+    }
+}
+
+/// Answers requests from a directory written by `RecordingTransport` without
+/// touching the network. Identical requests are answered in recorded order,
+/// and the last answer is repeated once they run out.
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+impl ReplayTransport {
+    pub fn new(dir: &Path) -> Result<Self> {
+        let read_error =
+            |e: std::io::Error| Error::Input(format!("could not read {}: {}", dir.display(), e));
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(read_error)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        let mut interactions = Vec::new();
+        for path in paths {
+            let json = fs::read_to_string(&path).map_err(read_error)?;
+            let interaction: Interaction = serde_json::from_str(&json)
+                .map_err(|e| Error::Parse(format!("{}: {}", path.display(), e)))?;
+            interactions.push((interaction, false));
+        }
+        log::info!("replaying {} recorded requests", interactions.len());
+        Ok(ReplayTransport {
+            interactions: Mutex::new(interactions),
+        })
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let wanted = RecordedRequest::new(request);
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        let position = interactions
+            .iter()
+            .position(|(i, used)| !used && i.request.matches(&wanted))
+            .or_else(|| {
+                interactions
+                    .iter()
+                    .rposition(|(i, _)| i.request.matches(&wanted))
+            })
+            .ok_or_else(|| {
+                Error::Input(format!(
+                    "no recorded response for {} {}",
+                    wanted.method, request.url
+                ))
+            })?;
+        let (interaction, used) = &mut interactions[position];
+        *used = true;
+        let body = match &interaction.response.body {
+            Value::String(text) => text.clone().into_bytes(),
+            json => serde_json::to_vec(json)?,
+        };
+        Ok(Response {
+            status: interaction.response.status,
+            retry_after: interaction.response.retry_after,
+            body,
+        })
+    }
+}
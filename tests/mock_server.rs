@@ -0,0 +1,398 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+
+/// Runs the bundled mock server on a free port for the lifetime of the value.
+struct MockServer {
+    child: Child,
+    url: String,
+}
+
+impl MockServer {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mock-server"))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start mock-server");
+        let mut url = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut url)
+            .unwrap();
+        MockServer {
+            child,
+            url: url.trim().to_string(),
+        }
+    }
+
+    fn slack(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_slack-rs"))
+            .args(["--db", ":memory:", "--api-url", &self.url])
+            .args(args)
+            .env("SLACK_TOKEN", "xoxp-mock")
+            .output()
+            .expect("failed to run slack-rs")
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+#[test]
+fn sync_pages_through_users_and_channels() {
+    let server = MockServer::start();
+    let output = server.slack(&["sync", "--team", "mock"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("fetched 3 users"), "{}", stderr);
+    assert!(stderr.contains("fetched 4 channels"), "{}", stderr);
+    // stderr is a pipe here, so no progress line is redrawn
+    assert!(!stderr.contains('\r'), "{:?}", stderr);
+}
+
+#[test]
+fn search_prints_matches() {
+    let server = MockServer::start();
+    let output = server.slack(&["search", "--team", "mock", "--keyword", "rolled back"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Rolled back deploy #981"), "{}", stdout);
+    assert!(!stdout.contains("elevated 5xx"), "{}", stdout);
+}
+
+#[test]
+fn slack_errors_exit_with_api_code() {
+    let server = MockServer::start();
+    let output = server.slack(&[
+        "thread",
+        "https://mock.slack.com/archives/C0MOCK01/p1600000000000100",
+    ]);
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("thread_not_found"), "{}", stderr);
+}
+
+#[test]
+fn token_file_wins_over_the_environment() {
+    let server = MockServer::start();
+    let file = std::env::temp_dir().join(format!("slack-rs-token-{}", std::process::id()));
+    std::fs::write(&file, "not-a-token\n").unwrap();
+    let output = server.slack(&[
+        "search",
+        "--team",
+        "mock",
+        "--keyword",
+        "rolled back",
+        "--token-file",
+        file.to_str().unwrap(),
+    ]);
+    let _ = std::fs::remove_file(&file);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unsupported token"), "{}", stderr);
+}
+
+#[test]
+fn replay_answers_from_a_recording() {
+    let dir = std::env::temp_dir().join(format!("slack-rs-vcr-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir_arg = dir.to_str().unwrap();
+    let search = ["search", "--team", "mock", "--keyword", "rolled back"];
+
+    let server = MockServer::start();
+    let recorded = server.slack(&[&["--record", dir_arg][..], &search].concat());
+    assert!(recorded.status.success(), "{:?}", recorded);
+    drop(server);
+    let saved = std::fs::read_to_string(dir.join("0001-search.messages.json")).unwrap();
+    assert!(!saved.contains("xoxp-mock"), "{}", saved);
+    // a read, so a GET that retries like every other read
+    assert!(saved.contains(r#""method": "GET""#), "{}", saved);
+
+    let replayed = Command::new(env!("CARGO_BIN_EXE_slack-rs"))
+        .args(["--db", ":memory:", "--replay", dir_arg])
+        .args(search)
+        .env_remove("SLACK_TOKEN")
+        .output()
+        .unwrap();
+    assert!(replayed.status.success(), "{:?}", replayed);
+    let stdout = String::from_utf8_lossy(&replayed.stdout);
+    assert!(stdout.contains("Rolled back deploy #981"), "{}", stdout);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn read_pages_until_the_limit() {
+    let server = MockServer::start();
+    let output = server.slack(&[
+        "read",
+        "https://mock.slack.com/archives/C0MOCK01/p1700000000000100",
+        "--count",
+        "2",
+        "--limit",
+        "3",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Rolled back deploy"), "{}", stdout);
+    assert!(stdout.contains("back to baseline"), "{}", stdout);
+    assert!(!stdout.contains("load balancer"), "{}", stdout);
+}
+
+#[test]
+fn read_starts_at_the_linked_message() {
+    let server = MockServer::start();
+    let link = "https://mock.slack.com/archives/C0MOCK01/p1700000000000100";
+    let output = server.slack(&["read", link, "--format", "ndjson"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let first: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    assert_eq!(first["ts"], "1700000000.000100", "{}", stdout);
+
+    let output = server.slack(&["export", link, "--scope", "read"]);
+    assert!(output.status.success(), "{:?}", output);
+    let markdown = String::from_utf8_lossy(&output.stdout);
+    let first = markdown.find("Seeing elevated 5xx").expect(&markdown);
+    let second = markdown.find("load balancer").expect(&markdown);
+    assert!(first < second, "{}", markdown);
+}
+
+#[test]
+fn read_threaded_keeps_threads_in_order() {
+    let server = MockServer::start();
+    let output = server.slack(&[
+        "read-threaded",
+        "https://mock.slack.com/archives/C0MOCK01/p1700000000000100",
+        "--concurrency",
+        "4",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // the linked parent and its replies come first, then the next thread
+    let order = [
+        "Seeing elevated 5xx",
+        "Paging the on-call",
+        "I'm on it",
+        "Rolled back deploy",
+        "errors are gone",
+    ];
+    let positions: Vec<usize> = order
+        .iter()
+        .map(|text| stdout.find(text).expect(&stdout))
+        .collect();
+    assert!(positions.is_sorted(), "{}", stdout);
+}
+
+#[test]
+fn thread_count_caps_replies_and_says_so() {
+    let server = MockServer::start();
+    let link = "https://mock.slack.com/archives/C0MOCK01/p1700000000000100";
+    let output = server.slack(&["thread", link, "--count", "2"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Paging the on-call"), "{}", stdout);
+    assert!(!stdout.contains("I'm on it"), "{}", stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("raise --count"), "{}", stderr);
+
+    let output = server.slack(&["thread", link]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("I'm on it"), "{}", stdout);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+
+    let output = server.slack(&["thread", link, "--count", "0"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn format_prints_resolved_records() {
+    let server = MockServer::start();
+    let link = "https://mock.slack.com/archives/C0MOCK01/p1700000000000100";
+    let output = server.slack(&["thread", link, "--format", "json"]);
+    assert!(output.status.success(), "{:?}", output);
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let first = &records[0];
+    assert_eq!(first["ts"], "1700000000.000100");
+    assert_eq!(first["time"], "2023-11-14T22:13:20Z");
+    assert_eq!(first["channel_id"], "C0MOCK01");
+    // nothing is synced, so the id stands in rather than a made up name
+    assert_eq!(first["user_name"], "U0MOCK01");
+    assert_eq!(first["permalink"], link);
+    assert_eq!(
+        first["text"],
+        "Seeing elevated 5xx on checkout, opening #incident-42"
+    );
+    let reply = &records[1];
+    assert_eq!(reply["thread_ts"], "1700000000.000100");
+    assert!(reply["permalink"].as_str().unwrap().contains("?thread_ts="));
+
+    let output = server.slack(&[
+        "search",
+        "--team",
+        "mock",
+        "--keyword",
+        "rolled back",
+        "--format",
+        "csv",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next(),
+        Some("ts,time,user_id,user_name,channel_id,channel,text,thread_ts,permalink")
+    );
+    let record = lines.next().unwrap();
+    // text is plain: links by their label, mentions by name, entities decoded
+    assert!(
+        record.contains(
+            "deploy #981 (https://example.com/deploys/981) after the checkout errors & paged @U0MOCK01,"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(record.contains(",U0MOCK03,carol,"), "{}", stdout);
+    assert!(!stdout.contains('\x1b'), "{}", stdout);
+}
+
+#[test]
+fn format_ends_quietly_when_the_reader_stops() {
+    let server = MockServer::start();
+    let link = "https://mock.slack.com/archives/C0MOCK01/p1700000000000100";
+    let mut child = Command::new(env!("CARGO_BIN_EXE_slack-rs"))
+        .args(["--db", ":memory:", "--api-url", &server.url])
+        .args(["thread", link, "--format", "ndjson"])
+        .env("SLACK_TOKEN", "xoxp-mock")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // like `head` exiting before anything is written
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn export_writes_a_thread_transcript() {
+    let server = MockServer::start();
+    let reply = "https://mock.slack.com/archives/C0MOCK01/p1700000180000310?thread_ts=1700000120.000300&cid=C0MOCK01";
+    let output = server.slack(&["export", reply]);
+    assert!(output.status.success(), "{:?}", output);
+    let markdown = String::from_utf8_lossy(&output.stdout);
+    assert!(markdown.starts_with("# #C0MOCK01\n"), "{}", markdown);
+    assert!(markdown.contains("**U0MOCK03** · ["), "{}", markdown);
+    assert!(
+        markdown.contains("[#981](https://example.com/deploys/981)"),
+        "{}",
+        markdown
+    );
+    assert!(
+        markdown.contains("> Confirmed, **errors are gone**"),
+        "{}",
+        markdown
+    );
+    assert!(!markdown.contains("Seeing elevated 5xx"), "{}", markdown);
+
+    let file = std::env::temp_dir().join(format!("slack-rs-export-{}.html", std::process::id()));
+    let output = server.slack(&[
+        "export",
+        "https://mock.slack.com/archives/C0MOCK01",
+        "--scope",
+        "read-threaded",
+        "--since",
+        "1700000000",
+        "--to",
+        "html",
+        "--output",
+        file.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty(), "{:?}", output);
+    let html = std::fs::read_to_string(&file).unwrap();
+    let _ = std::fs::remove_file(&file);
+    assert!(html.starts_with("<!DOCTYPE html>"), "{}", html);
+    assert!(html.contains("<section class=\"replies\">"), "{}", html);
+    assert!(html.contains("<p>Paging the on-call</p>"), "{}", html);
+    assert!(
+        html.contains("<blockquote>elevated 5xx</blockquote>"),
+        "{}",
+        html
+    );
+}
+
+#[test]
+fn read_renders_blocks_attachments_and_files() {
+    let server = MockServer::start();
+    let output = server.slack(&[
+        "read",
+        "https://mock.slack.com/archives/C0MOCK01",
+        "--since",
+        "1700000010",
+        "--until",
+        "1700000100",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("PagerDuty: Triggered: checkout 5xx > 2%"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Urgency: high"), "{}", stdout);
+    assert!(stdout.contains("PagerDuty · Incident #4242"), "{}", stdout);
+    assert!(stdout.contains("> Region: us-east-1"), "{}", stdout);
+    assert!(!stdout.contains("Acknowledge"), "{}", stdout);
+    assert!(
+        stdout.contains("file: Error rate (https://mock.slack.com/files/"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn read_shows_times_edits_and_replies() {
+    let server = MockServer::start();
+    let output = server.slack(&[
+        "read",
+        "https://mock.slack.com/archives/C0MOCK01",
+        "--since",
+        "1699999999",
+        "--tz",
+        "Asia/Kolkata",
+        "--date-headers",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("── Wednesday, 15 November 2023 ──"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("03:43 "), "{}", stdout);
+    assert!(
+        stdout.contains("2 replies, last 2023-11-15 03:43"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("1 reply, last 2023-11-15 03:46"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("back to baseline :tada: (edited)"),
+        "{}",
+        stdout
+    );
+
+    let link = "https://mock.slack.com/archives/C0MOCK01/p1700000000000100";
+    let output = server.slack(&["thread", link, "--time-format", "iso", "--tz", "UTC"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2023-11-14T22:13:30+00:00 "), "{}", stdout);
+
+    let output = server.slack(&["thread", link, "--tz", "@nobody"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}